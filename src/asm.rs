@@ -0,0 +1,546 @@
+// an assembler for the classic syntax of Cowgod's reference, the one
+// printed by the disassembler, so its listings assemble back into the
+// same rom. Besides the instructions it knows:
+//
+//     label:                  the address of the next instruction or data
+//     NAME EQU expr           a constant
+//     ORG expr                moves to another address
+//     DB expr, "text", ...    bytes
+//     DW expr, ...            16 bits words, big endian
+//     INCLUDE "file"          the lines of another file, relative to this one
+//
+// Expressions use numbers (decimal, 0x/#/$ hex, 0b binary, 'c'), labels,
+// constants, parentheses and the operators of C. Comments start with ;
+use crate::cpu::{Chip8, MEMORY_SIZE};
+use crate::error::AsmError;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// nested includes deeper than this are most likely a loop, as
+// are constants defined through too many other constants
+const MAX_INCLUDE_DEPTH: usize = 16;
+const MAX_CONSTANT_DEPTH: usize = 64;
+
+// assembles a program whose includes are relative to the current directory
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut assembler = Assembler::new();
+    assembler.read(source, "<source>", Path::new(""), 0)?;
+    assembler.output()
+}
+
+pub fn assemble_file(path: &str) -> Result<Vec<u8>, AsmError> {
+    let source = fs::read_to_string(path).map_err(|e| AsmError {
+        file: path.to_string(),
+        line: 0,
+        message: e.to_string(),
+    })?;
+    let dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+    let mut assembler = Assembler::new();
+    assembler.read(&source, path, dir, 0)?;
+    assembler.output()
+}
+
+// where a line comes from, for the errors
+#[derive(Clone)]
+struct Location {
+    file: String,
+    line: usize,
+}
+
+impl Location {
+    fn error(&self, message: impl Into<String>) -> AsmError {
+        AsmError {
+            file: self.file.clone(),
+            line: self.line,
+            message: message.into(),
+        }
+    }
+}
+
+enum Symbol {
+    Label(i64),
+    // evaluated when used, so it can refer to labels defined later
+    Constant(String, Location),
+}
+
+enum Statement {
+    // DB and DW, width being 1 or 2 bytes
+    Data { width: usize, values: Vec<String> },
+    Instruction { mnemonic: String, operands: Vec<String> },
+}
+
+struct Assembler {
+    // the statements and their addresses, from the first pass
+    statements: Vec<(usize, Statement, Location)>,
+    symbols: HashMap<String, Symbol>,
+    addr: usize,
+}
+
+impl Assembler {
+    fn new() -> Self {
+        Assembler {
+            statements: Vec::new(),
+            symbols: HashMap::new(),
+            addr: Chip8::START_ADDR,
+        }
+    }
+
+    // first pass: gives an address to every label and statement
+    fn read(&mut self, source: &str, file: &str, dir: &Path, depth: usize) -> Result<(), AsmError> {
+        for (i, line) in source.lines().enumerate() {
+            let at = Location {
+                file: file.to_string(),
+                line: i + 1,
+            };
+            let mut line = strip_comment(line).trim();
+
+            // any number of labels can precede the statement
+            while let Some(colon) = line.find(':') {
+                let name = line[..colon].trim();
+                if !is_identifier(name) {
+                    break;
+                }
+                self.define(name, Symbol::Label(self.addr as i64), &at)?;
+                line = line[colon + 1..].trim();
+            }
+            if line.is_empty() {
+                continue;
+            }
+
+            let (word, rest) = split_word(line);
+            let (second, value) = split_word(rest);
+            if second.eq_ignore_ascii_case("EQU") {
+                if !is_identifier(word) {
+                    return Err(at.error(format!("invalid constant name {}", word)));
+                }
+                self.define(word, Symbol::Constant(value.to_string(), at.clone()), &at)?;
+                continue;
+            }
+
+            let operands = split_operands(rest);
+            match word.to_uppercase().as_str() {
+                "ORG" => {
+                    let addr = self.eval(rest, &at)?;
+                    if addr < Chip8::START_ADDR as i64 || addr >= MEMORY_SIZE as i64 {
+                        return Err(at.error(format!("ORG {:#X} is outside of the rom", addr)));
+                    }
+                    self.addr = addr as usize;
+                }
+                "INCLUDE" => {
+                    let name = rest.trim().trim_matches('"');
+                    if depth == MAX_INCLUDE_DEPTH {
+                        return Err(at.error("too many nested includes"));
+                    }
+                    let path = dir.join(name);
+                    let source = fs::read_to_string(&path)
+                        .map_err(|e| at.error(format!("{}: {}", path.display(), e)))?;
+                    let inner: PathBuf = path.parent().map(Path::to_path_buf).unwrap_or_default();
+                    self.read(&source, &path.display().to_string(), &inner, depth + 1)?;
+                }
+                "DB" | "DW" => {
+                    let width = if word.eq_ignore_ascii_case("DB") { 1 } else { 2 };
+                    let mut size = 0;
+                    for value in operands.iter() {
+                        size += match string_literal(value) {
+                            Some(text) if width == 1 => text.len(),
+                            _ => width,
+                        };
+                    }
+                    self.push(size, Statement::Data { width, values: operands }, at)?;
+                }
+                mnemonic => {
+                    // only F000 NNNN is 4 bytes long
+                    let long = mnemonic == "LD"
+                        && operands.get(1).is_some_and(|op| split_word(op).0.eq_ignore_ascii_case("LONG"));
+                    let statement = Statement::Instruction {
+                        mnemonic: mnemonic.to_string(),
+                        operands,
+                    };
+                    self.push(if long { 4 } else { 2 }, statement, at)?;
+                }
+            }
+        }
+        Ok(())
+    }
+    fn define(&mut self, name: &str, symbol: Symbol, at: &Location) -> Result<(), AsmError> {
+        if self.symbols.contains_key(name) {
+            return Err(at.error(format!("{} is already defined", name)));
+        }
+        self.symbols.insert(name.to_string(), symbol);
+        Ok(())
+    }
+    fn push(&mut self, size: usize, statement: Statement, at: Location) -> Result<(), AsmError> {
+        if self.addr + size > MEMORY_SIZE {
+            return Err(at.error("the program doesn't fit in memory"));
+        }
+        self.statements.push((self.addr, statement, at));
+        self.addr += size;
+        Ok(())
+    }
+
+    // second pass: now that every label is known, encodes the statements
+    fn output(&self) -> Result<Vec<u8>, AsmError> {
+        let mut rom = Vec::new();
+        for (addr, statement, at) in self.statements.iter() {
+            let bytes = match statement {
+                Statement::Data { width, values } => self.data(*width, values, at)?,
+                Statement::Instruction { mnemonic, operands } => self.instruction(mnemonic, operands, at)?,
+            };
+            let start = addr - Chip8::START_ADDR;
+            if rom.len() < start + bytes.len() {
+                rom.resize(start + bytes.len(), 0);
+            }
+            rom[start..start + bytes.len()].copy_from_slice(&bytes);
+        }
+        Ok(rom)
+    }
+    fn data(&self, width: usize, values: &[String], at: &Location) -> Result<Vec<u8>, AsmError> {
+        let mut bytes = Vec::new();
+        for value in values {
+            match string_literal(value) {
+                Some(text) if width == 1 => bytes.extend_from_slice(text.as_bytes()),
+                _ if width == 1 => bytes.push(self.value(value, 0xFF, at)? as u8),
+                _ => bytes.extend_from_slice(&(self.value(value, 0xFFFF, at)? as u16).to_be_bytes()),
+            }
+        }
+        Ok(bytes)
+    }
+
+    fn instruction(&self, mnemonic: &str, operands: &[String], at: &Location) -> Result<Vec<u8>, AsmError> {
+        let ops: Vec<Operand> = operands.iter().map(|op| Operand::parse(op)).collect();
+        let addr = |op: &str| self.value(op, 0xFFF, at);
+        let byte = |op: &str| self.value(op, 0xFF, at);
+        let nibble = |op: &str| self.value(op, 0xF, at);
+        use Operand::*;
+        let opcode = match (mnemonic, ops.as_slice()) {
+            ("CLS", []) => 0x00E0,
+            ("RET", []) => 0x00EE,
+            ("SCR", []) => 0x00FB,
+            ("SCL", []) => 0x00FC,
+            ("EXIT", []) => 0x00FD,
+            ("LOW", []) => 0x00FE,
+            ("HIGH", []) => 0x00FF,
+            ("SCD", [Expr(n)]) => 0x00C0 | nibble(n)?,
+            ("SCU", [Expr(n)]) => 0x00D0 | nibble(n)?,
+            ("JP", [Expr(nnn)]) => 0x1000 | addr(nnn)?,
+            ("JP", [V(0), Expr(nnn)]) => 0xB000 | addr(nnn)?,
+            ("CALL", [Expr(nnn)]) => 0x2000 | addr(nnn)?,
+            ("SE", [V(x), V(y)]) => 0x5000 | x << 8 | y << 4,
+            ("SE", [V(x), Expr(nn)]) => 0x3000 | x << 8 | byte(nn)?,
+            ("SNE", [V(x), V(y)]) => 0x9000 | x << 8 | y << 4,
+            ("SNE", [V(x), Expr(nn)]) => 0x4000 | x << 8 | byte(nn)?,
+            ("SAVE", [V(x), V(y)]) => 0x5002 | x << 8 | y << 4,
+            ("LOAD", [V(x), V(y)]) => 0x5003 | x << 8 | y << 4,
+            ("LD", [V(x), V(y)]) => 0x8000 | x << 8 | y << 4,
+            ("LD", [V(x), Dt]) => 0xF007 | x << 8,
+            ("LD", [V(x), K]) => 0xF00A | x << 8,
+            ("LD", [V(x), MemI]) => 0xF065 | x << 8,
+            ("LD", [V(x), R]) => 0xF085 | x << 8,
+            ("LD", [V(x), Expr(nn)]) => 0x6000 | x << 8 | byte(nn)?,
+            ("LD", [I, Long(nnnn)]) => {
+                let nnnn = self.value(nnnn, 0xFFFF, at)?;
+                return Ok(vec![0xF0, 0x00, (nnnn >> 8) as u8, nnnn as u8]);
+            }
+            ("LD", [I, Expr(nnn)]) => 0xA000 | addr(nnn)?,
+            ("LD", [Dt, V(x)]) => 0xF015 | x << 8,
+            ("LD", [St, V(x)]) => 0xF018 | x << 8,
+            ("LD", [F, V(x)]) => 0xF029 | x << 8,
+            ("LD", [Hf, V(x)]) => 0xF030 | x << 8,
+            ("LD", [B, V(x)]) => 0xF033 | x << 8,
+            ("LD", [MemI, V(x)]) => 0xF055 | x << 8,
+            ("LD", [R, V(x)]) => 0xF075 | x << 8,
+            ("ADD", [V(x), V(y)]) => 0x8004 | x << 8 | y << 4,
+            ("ADD", [V(x), Expr(nn)]) => 0x7000 | x << 8 | byte(nn)?,
+            ("ADD", [I, V(x)]) => 0xF01E | x << 8,
+            ("OR", [V(x), V(y)]) => 0x8001 | x << 8 | y << 4,
+            ("AND", [V(x), V(y)]) => 0x8002 | x << 8 | y << 4,
+            ("XOR", [V(x), V(y)]) => 0x8003 | x << 8 | y << 4,
+            ("SUB", [V(x), V(y)]) => 0x8005 | x << 8 | y << 4,
+            ("SHR", [V(x)]) => 0x8006 | x << 8 | x << 4,
+            ("SHR", [V(x), V(y)]) => 0x8006 | x << 8 | y << 4,
+            ("SUBN", [V(x), V(y)]) => 0x8007 | x << 8 | y << 4,
+            ("SHL", [V(x)]) => 0x800E | x << 8 | x << 4,
+            ("SHL", [V(x), V(y)]) => 0x800E | x << 8 | y << 4,
+            ("RND", [V(x), Expr(nn)]) => 0xC000 | x << 8 | byte(nn)?,
+            ("DRW", [V(x), V(y), Expr(n)]) => 0xD000 | x << 8 | y << 4 | nibble(n)?,
+            ("SKP", [V(x)]) => 0xE09E | x << 8,
+            ("SKNP", [V(x)]) => 0xE0A1 | x << 8,
+            ("PLANE", [Expr(n)]) => 0xF001 | nibble(n)? << 8,
+            ("AUDIO", []) => 0xF002,
+            ("PITCH", [V(x)]) => 0xF03A | x << 8,
+            _ => {
+                return Err(at.error(format!(
+                    "invalid instruction {} {}",
+                    mnemonic,
+                    operands.join(", ")
+                )))
+            }
+        };
+        Ok(vec![(opcode >> 8) as u8, opcode as u8])
+    }
+
+    // an expression that must fit in max, negative numbers are
+    // accepted down to -(max + 1) / 2 as two's complement
+    fn value(&self, expr: &str, max: usize, at: &Location) -> Result<usize, AsmError> {
+        let value = self.eval(expr, at)?;
+        if value > max as i64 || value < -(max as i64 + 1) / 2 {
+            return Err(at.error(format!("{} doesn't fit in {} bits", value, 64 - max.leading_zeros())));
+        }
+        Ok(value as usize & max)
+    }
+    fn eval(&self, expr: &str, at: &Location) -> Result<i64, AsmError> {
+        self.eval_nested(expr, at, 0)
+    }
+    fn eval_nested(&self, expr: &str, at: &Location, depth: usize) -> Result<i64, AsmError> {
+        let tokens = tokenize(expr).map_err(|e| at.error(e))?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            pos: 0,
+            symbol: &|name: &str| match self.symbols.get(name) {
+                Some(Symbol::Label(addr)) => Ok(*addr),
+                Some(Symbol::Constant(_, _)) if depth == MAX_CONSTANT_DEPTH => {
+                    Err(format!("{} is defined in terms of itself", name))
+                }
+                Some(Symbol::Constant(expr, at)) => {
+                    self.eval_nested(expr, at, depth + 1).map_err(|e| e.message)
+                }
+                None => Err(format!("undefined symbol {}", name)),
+            },
+        };
+        let value = parser.expr(0).map_err(|e| at.error(e))?;
+        if parser.pos != tokens.len() {
+            return Err(at.error(format!("invalid expression {}", expr.trim())));
+        }
+        Ok(value)
+    }
+}
+
+// an operand of an instruction
+enum Operand<'a> {
+    V(usize),
+    I,
+    // [I]
+    MemI,
+    Dt,
+    St,
+    K,
+    F,
+    Hf,
+    B,
+    R,
+    Long(&'a str),
+    Expr(&'a str),
+}
+
+impl<'a> Operand<'a> {
+    fn parse(op: &'a str) -> Operand<'a> {
+        let upper = op.to_uppercase();
+        match upper.as_str() {
+            "I" => return Operand::I,
+            "[I]" => return Operand::MemI,
+            "DT" => return Operand::Dt,
+            "ST" => return Operand::St,
+            "K" => return Operand::K,
+            "F" => return Operand::F,
+            "HF" => return Operand::Hf,
+            "B" => return Operand::B,
+            "R" => return Operand::R,
+            _ => (),
+        }
+        if upper.len() == 2 && upper.starts_with('V') {
+            if let Ok(x) = usize::from_str_radix(&upper[1..], 16) {
+                return Operand::V(x);
+            }
+        }
+        match split_word(op) {
+            (word, rest) if word.eq_ignore_ascii_case("LONG") => Operand::Long(rest),
+            _ => Operand::Expr(op),
+        }
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+// the first word and the rest of the line
+fn split_word(line: &str) -> (&str, &str) {
+    let line = line.trim();
+    match line.find(char::is_whitespace) {
+        Some(i) => (&line[..i], line[i..].trim()),
+        None => (line, ""),
+    }
+}
+
+// the text up to a ; that isn't in a string or a character
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (None, ';') => return &line[..i],
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            _ => (),
+        }
+    }
+    line
+}
+
+// splits on the commas outside of strings
+fn split_operands(text: &str) -> Vec<String> {
+    let mut operands = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+    for c in text.chars() {
+        match (quote, c) {
+            (None, ',') => operands.push(std::mem::take(&mut current)),
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                current.push(c);
+            }
+            (Some(q), c) if q == c => {
+                quote = None;
+                current.push(c);
+            }
+            _ => current.push(c),
+        }
+    }
+    if !current.trim().is_empty() || !operands.is_empty() {
+        operands.push(current);
+    }
+    operands.iter().map(|op| op.trim().to_string()).collect()
+}
+
+fn string_literal(value: &str) -> Option<&str> {
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        Some(&value[1..value.len() - 1])
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(i64),
+    Name(String),
+    // an operator or a parenthesis, << and >> are a single token
+    Op(&'static str),
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, String> {
+    const OPS: [&str; 13] = ["<<", ">>", "+", "-", "*", "/", "%", "&", "|", "^", "~", "(", ")"];
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let rest: String = chars[i..].iter().collect();
+        if c.is_whitespace() {
+            i += 1;
+        } else if let Some(op) = OPS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Op(op));
+            i += op.len();
+        } else if c == '\'' {
+            match (chars.get(i + 1), chars.get(i + 2)) {
+                (Some(c), Some('\'')) => tokens.push(Token::Num(*c as i64)),
+                _ => return Err(format!("invalid character in {}", expr.trim())),
+            }
+            i += 3;
+        } else if c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '#' || c == '$' {
+            let len = chars[i + 1..]
+                .iter()
+                .take_while(|c| c.is_ascii_alphanumeric() || **c == '_' || **c == '.')
+                .count()
+                + 1;
+            let word: String = chars[i..i + len].iter().collect();
+            tokens.push(if c.is_ascii_digit() || c == '#' || c == '$' {
+                Token::Num(parse_number(&word).ok_or(format!("invalid number {}", word))?)
+            } else {
+                Token::Name(word)
+            });
+            i += len;
+        } else {
+            return Err(format!("unexpected {} in {}", c, expr.trim()));
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_number(word: &str) -> Option<i64> {
+    let lower = word.to_lowercase();
+    let (digits, radix) = if let Some(hex) = lower.strip_prefix("0x") {
+        (hex, 16)
+    } else if let Some(hex) = lower.strip_prefix('#').or_else(|| lower.strip_prefix('$')) {
+        (hex, 16)
+    } else if let Some(bin) = lower.strip_prefix("0b") {
+        (bin, 2)
+    } else {
+        (lower.as_str(), 10)
+    };
+    i64::from_str_radix(digits, radix).ok()
+}
+
+// a precedence climbing parser, the levels go from | to * / %
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    symbol: &'a dyn Fn(&str) -> Result<i64, String>,
+}
+
+const LEVELS: [&[&str]; 6] = [&["|"], &["^"], &["&"], &["<<", ">>"], &["+", "-"], &["*", "/", "%"]];
+
+impl<'a> Parser<'a> {
+    fn next(&mut self) -> Option<&'a Token> {
+        let token = self.tokens.get(self.pos);
+        self.pos += 1;
+        token
+    }
+    fn expr(&mut self, level: usize) -> Result<i64, String> {
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut value = self.expr(level + 1)?;
+        while let Some(Token::Op(op)) = self.tokens.get(self.pos) {
+            if !LEVELS[level].contains(op) {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.expr(level + 1)?;
+            value = match *op {
+                "|" => value | rhs,
+                "^" => value ^ rhs,
+                "&" => value & rhs,
+                "<<" => value.checked_shl(rhs as u32).unwrap_or(0),
+                ">>" => value.checked_shr(rhs as u32).unwrap_or(0),
+                "+" => value.wrapping_add(rhs),
+                "-" => value.wrapping_sub(rhs),
+                "*" => value.wrapping_mul(rhs),
+                _ if rhs == 0 => return Err("division by zero".to_string()),
+                "/" => value / rhs,
+                _ => value % rhs,
+            };
+        }
+        Ok(value)
+    }
+    fn unary(&mut self) -> Result<i64, String> {
+        match self.next() {
+            Some(Token::Num(n)) => Ok(*n),
+            Some(Token::Name(name)) => (self.symbol)(name),
+            Some(Token::Op("-")) => Ok(self.unary()?.wrapping_neg()),
+            Some(Token::Op("+")) => self.unary(),
+            Some(Token::Op("~")) => Ok(!self.unary()?),
+            Some(Token::Op("(")) => {
+                let value = self.expr(0)?;
+                match self.next() {
+                    Some(Token::Op(")")) => Ok(value),
+                    _ => Err("missing )".to_string()),
+                }
+            }
+            Some(Token::Op(op)) => Err(format!("unexpected {}", op)),
+            None => Err("missing value".to_string()),
+        }
+    }
+}
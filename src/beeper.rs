@@ -0,0 +1,158 @@
+use ggez::audio::{self, SoundSource};
+use ggez::{Context, GameResult};
+
+// the chip-8 has a single buzzer that sounds while the
+// sound timer is above zero
+pub trait Beeper {
+    // starts or stops the beep, called every frame
+    fn set_beeping(&mut self, beeping: bool);
+    // silences the beeper without losing its state
+    fn toggle_mute(&mut self);
+    // replaces the square wave by a XO-CHIP audio pattern
+    // played at rate bits per second
+    fn set_pattern(&mut self, ctx: &mut Context, pattern: &[u8; 16], rate: f32) -> GameResult;
+}
+
+// plays a square wave through the ggez audio device
+pub struct SquareBeeper {
+    source: audio::Source,
+    volume: f32,
+    // the XO-CHIP pattern being played, if any
+    pattern: Option<([u8; 16], f32)>,
+    beeping: bool,
+    muted: bool,
+}
+
+impl SquareBeeper {
+    const SAMPLE_RATE: u32 = 44100;
+
+    // pitch in Hz and volume from 0.0 to 1.0
+    pub fn new(ctx: &mut Context, pitch: f32, volume: f32, muted: bool) -> GameResult<Self> {
+        let wav = square_wave(pitch, SquareBeeper::SAMPLE_RATE);
+        Ok(SquareBeeper {
+            source: SquareBeeper::source(ctx, &wav, volume)?,
+            volume,
+            pattern: None,
+            beeping: false,
+            muted,
+        })
+    }
+    fn source(ctx: &mut Context, wav: &[u8], volume: f32) -> GameResult<audio::Source> {
+        let mut source = audio::Source::from_data(ctx, audio::SoundData::from_bytes(wav))?;
+        source.set_volume(volume);
+        source.set_repeat(true);
+        // the sound keeps looping in the sink, beeping only pauses
+        // and resumes it
+        source.play()?;
+        source.pause();
+        Ok(source)
+    }
+    fn update(&self) {
+        if self.beeping && !self.muted {
+            self.source.resume();
+        } else {
+            self.source.pause();
+        }
+    }
+}
+
+impl Beeper for SquareBeeper {
+    fn set_beeping(&mut self, beeping: bool) {
+        if self.beeping != beeping {
+            self.beeping = beeping;
+            self.update();
+        }
+    }
+    fn toggle_mute(&mut self) {
+        self.muted = !self.muted;
+        self.update();
+    }
+    fn set_pattern(&mut self, ctx: &mut Context, pattern: &[u8; 16], rate: f32) -> GameResult {
+        if self.pattern == Some((*pattern, rate)) {
+            return Ok(());
+        }
+        let wav = pattern_wave(pattern, rate, SquareBeeper::SAMPLE_RATE);
+        self.source = SquareBeeper::source(ctx, &wav, self.volume)?;
+        self.pattern = Some((*pattern, rate));
+        self.update();
+        Ok(())
+    }
+}
+
+// used when there is no audio device, or it has been disabled
+pub struct NullBeeper;
+
+impl Beeper for NullBeeper {
+    fn set_beeping(&mut self, _beeping: bool) {}
+    fn toggle_mute(&mut self) {}
+    fn set_pattern(&mut self, _ctx: &mut Context, _pattern: &[u8; 16], _rate: f32) -> GameResult {
+        Ok(())
+    }
+}
+
+const AMPLITUDE: i16 = i16::MAX / 2;
+
+// builds a wav file with about one second of a square wave
+fn square_wave(pitch: f32, sample_rate: u32) -> Vec<u8> {
+    let samples = whole_periods(pitch, sample_rate, |period| {
+        if period.fract() < 0.5 {
+            AMPLITUDE
+        } else {
+            -AMPLITUDE
+        }
+    });
+    wav(samples, sample_rate)
+}
+
+// builds a wav file repeating the 128 bits of the pattern, the
+// most significant bit first, for about one second
+fn pattern_wave(pattern: &[u8; 16], rate: f32, sample_rate: u32) -> Vec<u8> {
+    let samples = whole_periods(rate / 128.0, sample_rate, |period| {
+        let bit = (period.fract() * 128.0) as usize;
+        if pattern[bit / 8] & (0x80 >> (bit % 8)) != 0 {
+            AMPLITUDE
+        } else {
+            -AMPLITUDE
+        }
+    });
+    wav(samples, sample_rate)
+}
+
+// about one second of a sound repeated frequency times per second.
+// A period rarely lasts a whole number of samples, so the frequency
+// is adjusted a little to end a period exactly at the last sample,
+// otherwise the loop would click when it starts over. The sample
+// function is given the position in periods, like 2.5 for the middle
+// of the third one
+fn whole_periods(frequency: f32, sample_rate: u32, sample: impl Fn(f64) -> i16) -> Vec<i16> {
+    let periods = frequency.round().max(1.0) as f64;
+    let len = (periods * sample_rate as f64 / frequency as f64).round().max(1.0) as usize;
+    (0..len).map(|i| sample(i as f64 * periods / len as f64)).collect()
+}
+
+// wraps 16 bits mono samples in a wav file
+fn wav(samples: Vec<i16>, sample_rate: u32) -> Vec<u8> {
+    let data_len = (samples.len() * 2) as u32;
+
+    let mut wav = Vec::with_capacity(44 + samples.len() * 2);
+    // RIFF header
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+    // format chunk: PCM, mono, 16 bits
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    // the samples
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+    wav
+}
@@ -0,0 +1,58 @@
+// assembles a program written in the syntax of chip8-disasm, or
+// compiles an Octo program when the source ends with .8o:
+//
+//     chip8-asm SOURCE [--output ROM]
+//
+// without --output the rom is written next to the source, with
+// the .ch8 extension
+use chip8::{asm, octo};
+use std::fs;
+use std::path::Path;
+use std::process;
+
+fn usage(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    eprintln!("usage: chip8-asm SOURCE [--output ROM]");
+    process::exit(2);
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut source = None;
+    let mut output = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" | "-o" => {
+                output = Some(args.next().unwrap_or_else(|| usage(&format!("{} needs a value", arg))))
+            }
+            _ if arg.starts_with('-') => usage(&format!("unknown option {}", arg)),
+            _ if source.is_some() => usage(&format!("unexpected argument {}", arg)),
+            _ => source = Some(arg),
+        }
+    }
+    let source = source.unwrap_or_else(|| usage("no source given"));
+    let output = output.unwrap_or_else(|| {
+        Path::new(&source)
+            .with_extension("ch8")
+            .to_string_lossy()
+            .into_owned()
+    });
+
+    let compiled = if source.ends_with(".8o") {
+        octo::compile_file(&source)
+    } else {
+        asm::assemble_file(&source)
+    };
+    let rom = match compiled {
+        Ok(rom) => rom,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    if let Err(e) = fs::write(&output, &rom) {
+        eprintln!("{}: {}", output, e);
+        process::exit(1);
+    }
+    println!("{}: {} bytes", output, rom.len());
+}
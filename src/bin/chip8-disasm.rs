@@ -0,0 +1,60 @@
+// prints the listing of a rom:
+//
+//     chip8-disasm ROM [--syntax octo|cowgod] [--origin ADDR]
+use chip8::disasm::{self, Syntax};
+use std::fs;
+use std::process;
+
+fn usage(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    eprintln!("usage: chip8-disasm ROM [--syntax octo|cowgod] [--origin ADDR]");
+    process::exit(2);
+}
+
+// numbers may be given in decimal or in hex with a 0x prefix
+fn parse_num(s: &str) -> Option<usize> {
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        usize::from_str_radix(hex, 16).ok()
+    } else {
+        s.parse().ok()
+    }
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut rom = None;
+    let mut syntax = Syntax::Cowgod;
+    let mut origin = 0x200;
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            if rom.is_some() {
+                usage(&format!("unexpected argument {}", arg));
+            }
+            rom = Some(arg);
+            continue;
+        }
+        let value = args
+            .next()
+            .unwrap_or_else(|| usage(&format!("{} needs a value", arg)));
+        match arg.as_str() {
+            "--syntax" => {
+                syntax = Syntax::from_name(&value)
+                    .unwrap_or_else(|| usage(&format!("unknown syntax {}", value)))
+            }
+            "--origin" => {
+                origin = parse_num(&value).unwrap_or_else(|| usage(&format!("invalid number {}", value)))
+            }
+            _ => usage(&format!("unknown option {}", arg)),
+        }
+    }
+    let rom = rom.unwrap_or_else(|| usage("no rom given"));
+
+    let bytes = match fs::read(&rom) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("{}: {}", rom, e);
+            process::exit(1);
+        }
+    };
+    print!("{}", disasm::listing(&bytes, origin, syntax));
+}
@@ -0,0 +1,94 @@
+// waits for gdb (or any other client of the remote serial protocol) on
+// a local port, and lets it drive a rom until it detaches:
+//
+//     chip8-gdb ROM [--port N] [--ipf N] [--quirks PRESET] [--seed N]
+//
+// then in gdb: target remote localhost:1234
+use chip8::gdb::GdbStub;
+use chip8::runner::DEFAULT_IPF;
+use chip8::{Chip8, Quirks};
+use std::net::TcpListener;
+use std::process;
+
+fn usage(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    eprintln!("usage: chip8-gdb ROM [--port N] [--ipf N] [--quirks PRESET] [--seed N]");
+    process::exit(2);
+}
+
+// numbers may be given in decimal or in hex with a 0x prefix
+fn parse_num(s: &str) -> Option<u64> {
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16).ok()
+    } else {
+        s.parse().ok()
+    }
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut rom = None;
+    let mut port = 1234;
+    let mut ipf = DEFAULT_IPF;
+    let mut quirks = Quirks::default();
+    let mut seed = None;
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            if rom.is_some() {
+                usage(&format!("unexpected argument {}", arg));
+            }
+            rom = Some(arg);
+            continue;
+        }
+        let value = args
+            .next()
+            .unwrap_or_else(|| usage(&format!("{} needs a value", arg)));
+        let num = || parse_num(&value).unwrap_or_else(|| usage(&format!("invalid number {}", value)));
+        match arg.as_str() {
+            "--port" => port = num().min(0xFFFF) as u16,
+            "--ipf" => match num() {
+                0 => usage("--ipf must be at least 1"),
+                n => ipf = n as usize,
+            },
+            "--quirks" => {
+                quirks = Quirks::preset(&value)
+                    .unwrap_or_else(|| usage(&format!("unknown quirks preset {}", value)))
+            }
+            "--seed" => seed = Some(num()),
+            _ => usage(&format!("unknown option {}", arg)),
+        }
+    }
+    let rom = rom.unwrap_or_else(|| usage("no rom given"));
+
+    let mut chip8 = Chip8::with_quirks(quirks);
+    if let Some(seed) = seed {
+        chip8.seed(seed);
+    }
+    // Octo sources are compiled on the fly
+    let loaded = if rom.ends_with(".8o") {
+        chip8.load_octo_file(&rom).map_err(|e| e.to_string())
+    } else {
+        chip8.load_rom(&rom).map_err(|e| format!("{}: {}", rom, e))
+    };
+    if let Err(e) = loaded {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+
+    // only local clients, the protocol has no authentication
+    let listener = match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("port {}: {}", port, e);
+            process::exit(1);
+        }
+    };
+    println!("waiting for gdb on localhost:{}", port);
+    let result = listener
+        .accept()
+        .and_then(|(stream, _)| GdbStub::new(ipf).serve(&mut chip8, stream));
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,299 @@
+// runs a rom without a window, for CI and scripts. It prints the
+// state of the machine when it stops:
+//
+//     chip8-headless ROM [--frames N] [--ipf N] [--quirks PRESET]
+//                        [--until-pc ADDR] [--until-opcode OPCODE] [--max-cycles N]
+//                        [--press FRAME:KEY[:FRAMES]]... [--seed N]
+//                        [--dump-memory FILE] [--screenshot FILE.{pbm,pgm,png}]
+//                        [--trace FILE] [--trace-range START:END] [--trace-last N]
+//                        [--no-database]
+//
+// the roms known by the rom database run with their quirks and speed,
+// unless --quirks/--ipf (or --no-database) say otherwise
+//
+// --trace logs every instruction to FILE (- for stdout), with --trace-last
+// only the last N instructions are written, and only if the rom fails
+use chip8::runner::{Press, Stop, DEFAULT_IPF};
+use chip8::{snapshot, Chip8, Database, Quirks, Runner, Tracer};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::process;
+
+struct Options {
+    rom: String,
+    frames: u64,
+    ipf: Option<usize>,
+    quirks: Option<Quirks>,
+    database: bool,
+    until_pc: Option<usize>,
+    until_opcode: Option<usize>,
+    max_cycles: Option<u64>,
+    presses: Vec<Press>,
+    seed: Option<u64>,
+    dump_memory: Option<String>,
+    screenshot: Option<String>,
+    trace: Option<String>,
+    trace_range: Option<(usize, usize)>,
+    trace_last: Option<usize>,
+}
+
+fn usage(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    eprintln!(
+        "usage: chip8-headless ROM [--frames N] [--ipf N] [--quirks PRESET] \
+         [--until-pc ADDR] [--until-opcode OPCODE] [--max-cycles N] \
+         [--press FRAME:KEY[:FRAMES]]... [--seed N] \
+         [--dump-memory FILE] [--screenshot FILE.{{pbm,pgm,png}}] \
+         [--trace FILE] [--trace-range START:END] [--trace-last N] [--no-database]"
+    );
+    process::exit(2);
+}
+
+// numbers may be given in decimal or in hex with a 0x prefix
+fn parse_num(s: &str) -> Option<u64> {
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16).ok()
+    } else {
+        s.parse().ok()
+    }
+}
+
+fn parse_press(s: &str) -> Option<Press> {
+    let parts: Vec<&str> = s.split(':').collect();
+    if parts.len() < 2 || parts.len() > 3 {
+        return None;
+    }
+    let key = parse_num(parts[1])? as usize;
+    if key > 0xF {
+        return None;
+    }
+    Some(Press {
+        frame: parse_num(parts[0])?,
+        key,
+        frames: match parts.get(2) {
+            Some(frames) => parse_num(frames)?,
+            None => 1,
+        },
+    })
+}
+
+fn parse_range(s: &str) -> Option<(usize, usize)> {
+    let mut parts = s.split(':');
+    let start = parse_num(parts.next()?)? as usize;
+    let end = parse_num(parts.next()?)? as usize;
+    if parts.next().is_some() || start > end {
+        return None;
+    }
+    Some((start, end))
+}
+
+fn parse_args() -> Options {
+    let mut args = std::env::args().skip(1);
+    let mut options = Options {
+        rom: String::new(),
+        frames: 60,
+        ipf: None,
+        quirks: None,
+        database: true,
+        until_pc: None,
+        until_opcode: None,
+        max_cycles: None,
+        presses: Vec::new(),
+        seed: None,
+        dump_memory: None,
+        screenshot: None,
+        trace: None,
+        trace_range: None,
+        trace_last: None,
+    };
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            if !options.rom.is_empty() {
+                usage(&format!("unexpected argument {}", arg));
+            }
+            options.rom = arg;
+            continue;
+        }
+        if arg == "--no-database" {
+            options.database = false;
+            continue;
+        }
+        let value = args
+            .next()
+            .unwrap_or_else(|| usage(&format!("{} needs a value", arg)));
+        let num = || parse_num(&value).unwrap_or_else(|| usage(&format!("invalid number {}", value)));
+        match arg.as_str() {
+            "--frames" => options.frames = num(),
+            "--ipf" => match num() {
+                0 => usage("--ipf must be at least 1"),
+                ipf => options.ipf = Some(ipf as usize),
+            },
+            "--quirks" => {
+                options.quirks = Some(
+                    Quirks::preset(&value)
+                        .unwrap_or_else(|| usage(&format!("unknown quirks preset {}", value))),
+                )
+            }
+            "--until-pc" => options.until_pc = Some(num() as usize),
+            "--until-opcode" => options.until_opcode = Some(num() as usize),
+            "--max-cycles" => options.max_cycles = Some(num()),
+            "--press" => options.presses.push(
+                parse_press(&value).unwrap_or_else(|| usage(&format!("invalid key press {}", value))),
+            ),
+            "--seed" => options.seed = Some(num()),
+            "--dump-memory" => options.dump_memory = Some(value),
+            "--screenshot" => {
+                let ext = value.rsplit('.').next().unwrap_or("").to_lowercase();
+                if !value.contains('.') || !["pbm", "pgm", "png"].contains(&ext.as_str()) {
+                    usage(&format!("unknown image format {}", value));
+                }
+                options.screenshot = Some(value)
+            }
+            "--trace" => options.trace = Some(value),
+            "--trace-range" => {
+                options.trace_range =
+                    Some(parse_range(&value).unwrap_or_else(|| usage(&format!("invalid range {}", value))))
+            }
+            "--trace-last" => options.trace_last = Some(num() as usize),
+            _ => usage(&format!("unknown option {}", arg)),
+        }
+    }
+    if options.rom.is_empty() {
+        usage("no rom given");
+    }
+    if options.trace.is_none() && (options.trace_range.is_some() || options.trace_last.is_some()) {
+        usage("--trace-range and --trace-last need --trace");
+    }
+    options
+}
+
+fn main() {
+    let options = parse_args();
+
+    let mut chip8 = Chip8::init();
+    if let Some(seed) = options.seed {
+        chip8.seed(seed);
+    }
+    let database = if options.database {
+        Database::builtin()
+    } else {
+        Database::default()
+    };
+    // Octo sources are compiled on the fly, they can't be in the database
+    let loaded = if options.rom.ends_with(".8o") {
+        chip8.load_octo_file(&options.rom).map(|_| None).map_err(|e| e.to_string())
+    } else {
+        chip8
+            .load_rom_with(&options.rom, &database)
+            .map_err(|e| format!("{}: {}", options.rom, e))
+    };
+    let info = match loaded {
+        Ok(info) => info,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    if let Some(info) = &info {
+        println!("rom: {} ({})", info.title, info.platform);
+    }
+    if let Some(quirks) = options.quirks {
+        chip8.set_quirks(quirks);
+    }
+    let ipf = options
+        .ipf
+        .or_else(|| info.as_ref().and_then(|info| info.tickrate))
+        .unwrap_or(DEFAULT_IPF);
+
+    if let Some(path) = &options.trace {
+        let output: Box<dyn Write + Send> = if path == "-" {
+            Box::new(io::stdout())
+        } else {
+            match File::create(path) {
+                Ok(file) => Box::new(BufWriter::new(file)),
+                Err(e) => {
+                    eprintln!("{}: {}", path, e);
+                    process::exit(1);
+                }
+            }
+        };
+        let mut tracer = match options.trace_last {
+            Some(n) => Tracer::ring(output, n),
+            None => Tracer::new(output),
+        };
+        if let Some((start, end)) = options.trace_range {
+            tracer = tracer.with_range(start, end);
+        }
+        chip8.set_tracer(tracer);
+    }
+
+    let mut runner = Runner::new(ipf);
+    runner.frames = Some(options.frames);
+    runner.until_pc = options.until_pc;
+    runner.until_opcode = options.until_opcode;
+    runner.max_cycles = options.max_cycles;
+    runner.presses = options.presses;
+    let stop = runner.run(&mut chip8);
+    println!("stopped: {}", stop);
+    // dropping the tracer flushes the trace file
+    drop(chip8.take_tracer());
+    dump(&chip8, runner.frame(), runner.cycles());
+
+    if let Some(path) = options.dump_memory {
+        let memory: Vec<u8> = chip8.memory().iter().map(|b| *b as u8).collect();
+        if let Err(e) = fs::write(&path, memory) {
+            eprintln!("{}: {}", path, e);
+            process::exit(1);
+        }
+    }
+    if let Some(path) = options.screenshot {
+        let image = snapshot::export(&chip8, &path).expect("checked when parsing");
+        if let Err(e) = fs::write(&path, image) {
+            eprintln!("{}: {}", path, e);
+            process::exit(1);
+        }
+    }
+    if let Stop::Error(_) = stop {
+        process::exit(1);
+    }
+}
+
+// prints the registers and the screen, a lit pixel being #
+// (or the bitplanes number for XO-CHIP colours)
+fn dump(chip8: &Chip8, frame: u64, cycles: u64) {
+    println!("frames: {} cycles: {}", frame, cycles);
+    println!(
+        "pc: {:#05X} opcode: {:04X} i: {:#05X} sp: {} dt: {} st: {}",
+        chip8.pc(),
+        chip8.opcode(),
+        chip8.index(),
+        chip8.sp(),
+        chip8.delay_timer(),
+        chip8.sound_timer()
+    );
+    let regs: Vec<String> = chip8
+        .registers()
+        .iter()
+        .enumerate()
+        .map(|(i, v)| format!("v{:X}: {:02X}", i, v))
+        .collect();
+    println!("{}", regs.join(" "));
+    let stack: Vec<String> = chip8.stack()[..chip8.sp()]
+        .iter()
+        .map(|addr| format!("{:#05X}", addr))
+        .collect();
+    println!("stack: [{}]", stack.join(", "));
+
+    let width = chip8.width();
+    for row in chip8.framebuffer().chunks(width) {
+        let line: String = row
+            .iter()
+            .map(|pixel| match pixel {
+                0 => '.',
+                1 => '#',
+                p => (b'0' + p) as char,
+            })
+            .collect();
+        println!("{}", line);
+    }
+}
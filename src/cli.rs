@@ -0,0 +1,181 @@
+use crate::{DEFAULT_PITCH, DEFAULT_SCALE, DEFAULT_VOLUME, FRAME_RATE, MAX_PITCH, MAX_SCALE, MIN_PITCH};
+use chip8::database;
+use chip8::Quirks;
+use std::process;
+
+pub const HELP: &str = "usage: chip8 ROM [HZ] [OPTIONS]
+
+runs a CHIP-8 rom, or an Octo source (.8o), in a window. HZ is
+the same as --hz
+
+options:
+  --scale N          size of a CHIP-8 pixel on the screen, from 1 to 100 (20)
+  --ipf N            instructions executed per frame, at 60 frames a second
+  --hz N             instructions executed per second (500), instead of --ipf
+  --platform ID      runs the rom like on a platform of the rom database, with
+                     its quirks and speed (originalChip8, chip48, superchip, xochip...)
+  --quirks PRESET    vip, chip48, schip or xochip, on top of the platform
+  --palette COLORS   up to 4 colours as RRGGBB separated by commas, background first
+  --seed N           makes the random numbers reproducible
+  --mute             starts with the beeper muted, M unmutes it
+  --pitch HZ         pitch of the beep, from 20 to 20000 (440)
+  --volume N         volume of the sound in percent, from 0 to 100 (25)
+  --fullscreen       fills the screen
+  --headless         runs without a window or sound, until the rom exits or fails
+  --trace FILE       logs every executed instruction to FILE (- for stdout)
+  --config FILE      reads the settings from FILE instead of chip8.toml
+  -h, --help         prints this help
+
+the options win over the settings of the rom database";
+
+pub struct Options {
+    pub rom: String,
+    pub scale: usize,
+    // instructions per frame, from --ipf or --hz
+    pub ipf: Option<usize>,
+    pub platform: Option<String>,
+    pub quirks: Option<Quirks>,
+    pub palette: Vec<(u8, u8, u8)>,
+    pub seed: Option<u64>,
+    pub mute: bool,
+    // the beep in Hz, and the volume in percent
+    pub pitch: u32,
+    pub volume: u32,
+    pub fullscreen: bool,
+    pub headless: bool,
+    pub trace: Option<String>,
+    pub config: Option<String>,
+}
+
+pub fn usage(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    eprintln!("usage: chip8 ROM [HZ] [OPTIONS], --help lists them");
+    process::exit(2);
+}
+
+// numbers may be given in decimal or in hex with a 0x prefix
+fn parse_num(s: &str) -> Option<u64> {
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16).ok()
+    } else {
+        s.parse().ok()
+    }
+}
+
+// RRGGBB,RRGGBB... the # is optional, the shell would take
+// an argument starting with it for a comment
+fn parse_palette(s: &str) -> Option<Vec<(u8, u8, u8)>> {
+    let colors: Vec<&str> = s.split(',').collect();
+    if colors.len() > 4 {
+        return None;
+    }
+    colors
+        .iter()
+        .map(|color| database::parse_color(&format!("#{}", color.trim().trim_start_matches('#'))))
+        .collect()
+}
+
+pub fn parse(args: impl Iterator<Item = String>) -> Options {
+    let mut args = args;
+    let mut options = Options {
+        rom: String::new(),
+        scale: DEFAULT_SCALE,
+        ipf: None,
+        platform: None,
+        quirks: None,
+        palette: Vec::new(),
+        seed: None,
+        mute: false,
+        pitch: DEFAULT_PITCH,
+        volume: DEFAULT_VOLUME,
+        fullscreen: false,
+        headless: false,
+        trace: None,
+        config: None,
+    };
+    let mut hz = None;
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') {
+            // the speed used to be given after the rom, without --hz
+            if !options.rom.is_empty() && hz.is_none() && arg.parse::<usize>().is_ok() {
+                hz = Some(arg);
+                continue;
+            }
+            if !options.rom.is_empty() {
+                usage(&format!("unexpected argument {}", arg));
+            }
+            options.rom = arg;
+            continue;
+        }
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", HELP);
+                process::exit(0);
+            }
+            "--mute" => {
+                options.mute = true;
+                continue;
+            }
+            "--fullscreen" => {
+                options.fullscreen = true;
+                continue;
+            }
+            "--headless" => {
+                options.headless = true;
+                continue;
+            }
+            _ => (),
+        }
+        let value = args
+            .next()
+            .unwrap_or_else(|| usage(&format!("{} needs a value", arg)));
+        let num = || parse_num(&value).unwrap_or_else(|| usage(&format!("invalid number {}", value)));
+        match arg.as_str() {
+            "--scale" => match num() {
+                scale if scale >= 1 && scale <= MAX_SCALE as u64 => options.scale = scale as usize,
+                _ => usage(&format!("invalid scale {}, it must be between 1 and {}", value, MAX_SCALE)),
+            },
+            "--ipf" => match num() {
+                0 => usage("--ipf must be at least 1"),
+                ipf => options.ipf = Some(ipf as usize),
+            },
+            "--hz" => hz = Some(value),
+            "--platform" => options.platform = Some(value),
+            "--quirks" => {
+                options.quirks = Some(
+                    Quirks::preset(&value)
+                        .unwrap_or_else(|| usage(&format!("unknown quirks preset {}", value))),
+                )
+            }
+            "--palette" => {
+                options.palette =
+                    parse_palette(&value).unwrap_or_else(|| usage(&format!("invalid palette {}", value)))
+            }
+            "--pitch" => match num() {
+                pitch if pitch >= MIN_PITCH as u64 && pitch <= MAX_PITCH as u64 => options.pitch = pitch as u32,
+                _ => usage(&format!("invalid pitch {}, it must be between {} and {}", value, MIN_PITCH, MAX_PITCH)),
+            },
+            "--volume" => match num() {
+                volume if volume <= 100 => options.volume = volume as u32,
+                _ => usage(&format!("invalid volume {}, it must be between 0 and 100", value)),
+            },
+            "--seed" => options.seed = Some(num()),
+            "--trace" => options.trace = Some(value),
+            "--config" => options.config = Some(value),
+            _ => usage(&format!("unknown option {}", arg)),
+        }
+    }
+    if options.rom.is_empty() {
+        usage("no rom given");
+    }
+    if let Some(hz) = hz {
+        if options.ipf.is_some() {
+            usage("--ipf and --hz can't be used together");
+        }
+        match parse_num(&hz) {
+            Some(hz) if hz >= FRAME_RATE as u64 => options.ipf = Some(hz as usize / FRAME_RATE as usize),
+            _ => usage(&format!("invalid speed {}, it must be at least {}Hz", hz, FRAME_RATE)),
+        }
+    }
+    options
+}
@@ -0,0 +1,197 @@
+// the configuration file of the frontends, written in TOML:
+//
+//     # a full copy of the rom database, instead of the built-in one
+//     database = "/path/to/chip-8-database/database"
+//
+//     [keys]
+//     layout = "azerty"
+//     5 = ["Up", "Z"]
+//
+//     # overrides for a single rom, by SHA-1 or by file name
+//     [roms."BRIX".keys]
+//     4 = "Left"
+//     6 = "Right"
+//
+//     # the same for the buttons of a gamepad
+//     [gamepad]
+//     5 = ["South", "East"]
+//
+// The values are read with the toml crate, and the document is walked a
+// second time with toml_edit, which keeps where each key was written, so
+// the errors about a value can tell its line
+use crate::database::Database;
+use crate::error::ConfigError;
+use crate::keymap::Keymap;
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+pub use toml::{Table, Value};
+
+#[derive(Debug, Default)]
+pub struct Config {
+    file: String,
+    table: Table,
+    // the line where each value was defined, by dotted path
+    lines: HashMap<String, usize>,
+}
+
+impl Config {
+    pub fn parse(source: &str, file: &str) -> Result<Self, ConfigError> {
+        let table = source.parse::<Table>().map_err(|e| ConfigError {
+            file: file.to_string(),
+            line: e.span().map_or(0, |span| line(source, span)),
+            message: e.message().to_string(),
+        })?;
+        // it was parsed already, it can't fail
+        let document = toml_edit::ImDocument::parse(source).expect("valid TOML");
+        let mut lines = HashMap::new();
+        key_lines(source, document.as_item(), "", &mut lines);
+        Ok(Config {
+            file: file.to_string(),
+            table,
+            lines,
+        })
+    }
+    pub fn load(path: &str) -> Result<Self, ConfigError> {
+        let source = fs::read_to_string(path).map_err(|e| ConfigError {
+            file: path.to_string(),
+            line: 0,
+            message: e.to_string(),
+        })?;
+        Config::parse(&source, path)
+    }
+    pub fn table(&self) -> &Table {
+        &self.table
+    }
+    // the value at a dotted path, like keys.layout
+    pub fn get(&self, path: &str) -> Option<&Value> {
+        let mut parts = path.split('.');
+        let mut value = self.table.get(parts.next()?)?;
+        for part in parts {
+            value = value.as_table()?.get(part)?;
+        }
+        Some(value)
+    }
+
+    // the section overriding the settings of a rom, found by the hex
+    // SHA-1 of the rom or by its file name, with or without extension
+    pub fn rom_section(&self, hash: &str, path: &str) -> Option<(String, &Table)> {
+        let roms = self.table.get("roms")?.as_table()?;
+        let path = Path::new(path);
+        let names = [path.file_name(), path.file_stem()];
+        let names = names.iter().flatten().filter_map(|name| name.to_str());
+        for name in std::iter::once(hash).chain(names) {
+            if let Some(table) = roms.get(name).and_then(Value::as_table) {
+                return Some((format!("roms.{}", name), table));
+            }
+        }
+        None
+    }
+
+    // the [keys] table, then the one of the rom on top of it
+    pub fn keymap(&self, hash: &str, path: &str) -> Result<Keymap, ConfigError> {
+        self.mapping("keys", Keymap::default(), hash, path)
+    }
+    // the same with the [gamepad] tables, which have no layouts, on top
+    // of the given mapping (Keymap::gamepad or one made from key hints)
+    pub fn gamepad(&self, base: Keymap, hash: &str, path: &str) -> Result<Keymap, ConfigError> {
+        self.mapping("gamepad", base, hash, path)
+    }
+    // the rom database, the built-in one unless database names a
+    // directory with the files of the community database
+    pub fn database(&self) -> Result<Database, ConfigError> {
+        match self.table.get("database") {
+            None => Ok(Database::builtin()),
+            Some(Value::String(dir)) => Database::load(dir),
+            Some(_) => Err(self.error("database", "database should be a directory".to_string())),
+        }
+    }
+
+    fn mapping(&self, name: &str, mut keymap: Keymap, hash: &str, path: &str) -> Result<Keymap, ConfigError> {
+        let layouts = name == "keys";
+        if let Some(keys) = self.table.get(name) {
+            self.apply_keys(&mut keymap, name, keys, layouts)?;
+        }
+        if let Some((section, rom)) = self.rom_section(hash, path) {
+            if let Some(keys) = rom.get(name) {
+                self.apply_keys(&mut keymap, &format!("{}.{}", section, name), keys, layouts)?;
+            }
+        }
+        Ok(keymap)
+    }
+
+    fn apply_keys(&self, keymap: &mut Keymap, path: &str, keys: &Value, layouts: bool) -> Result<(), ConfigError> {
+        let keys = keys
+            .as_table()
+            .ok_or_else(|| self.error(path, format!("{} should be a table", path)))?;
+        // the layout first, the keys it doesn't set stay as they were
+        if let Some(layout) = keys.get("layout").filter(|_| layouts) {
+            let path = format!("{}.layout", path);
+            let name = layout
+                .as_str()
+                .ok_or_else(|| self.error(&path, format!("{} should be a string", path)))?;
+            *keymap = Keymap::layout(name)
+                .ok_or_else(|| self.error(&path, format!("unknown keyboard layout {}", name)))?;
+        }
+        for (name, value) in keys.iter().filter(|(name, _)| !layouts || *name != "layout") {
+            let path = format!("{}.{}", path, name);
+            let key = match usize::from_str_radix(name, 16) {
+                Ok(key) if key <= 0xF && name.len() == 1 => key,
+                _ => return Err(self.error(&path, format!("{} isn't a key of the keypad", name))),
+            };
+            let hosts = match value {
+                Value::String(host) => vec![host.clone()],
+                Value::Array(hosts) => hosts
+                    .iter()
+                    .map(|host| host.as_str().map(String::from))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| self.error(&path, format!("{} should only have strings", path)))?,
+                _ => {
+                    return Err(self.error(
+                        &path,
+                        format!("{} should be a key name or a list of them", path),
+                    ))
+                }
+            };
+            keymap.set(key, hosts);
+        }
+        Ok(())
+    }
+
+    // an error about the value at the given path
+    pub fn error(&self, path: &str, message: String) -> ConfigError {
+        ConfigError {
+            file: self.file.clone(),
+            line: self.lines.get(path).copied().unwrap_or(0),
+            message,
+        }
+    }
+}
+
+// records the line of each key, by dotted path
+fn key_lines(source: &str, item: &toml_edit::Item, path: &str, lines: &mut HashMap<String, usize>) {
+    let keys: Vec<(&toml_edit::Key, &toml_edit::Item)> = match item {
+        toml_edit::Item::Table(table) => table.iter().filter_map(|(name, _)| table.get_key_value(name)).collect(),
+        toml_edit::Item::Value(toml_edit::Value::InlineTable(table)) => {
+            table.iter().filter_map(|(name, _)| table.get_key_value(name)).collect()
+        }
+        _ => return,
+    };
+    for (key, item) in keys {
+        let path = if path.is_empty() {
+            key.get().to_string()
+        } else {
+            format!("{}.{}", path, key.get())
+        };
+        if let Some(span) = key.span() {
+            lines.entry(path.clone()).or_insert_with(|| line(source, span));
+        }
+        key_lines(source, item, &path, lines);
+    }
+}
+
+// the line of a position in the source, counting from 1
+fn line(source: &str, span: Range<usize>) -> usize {
+    source[..span.start].matches('\n').count() + 1
+}
@@ -0,0 +1,28 @@
+use std::io::{self, BufRead};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+// reads the debugger commands from the terminal, in a thread of its
+// own so the window keeps running while waiting for a line
+pub struct Console {
+    lines: Receiver<String>,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            // stops when the terminal is closed or the window quits
+            for line in io::stdin().lock().lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Console { lines }
+    }
+    // the next line typed, if any
+    pub fn poll(&self) -> Option<String> {
+        self.lines.try_recv().ok()
+    }
+}
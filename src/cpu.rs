@@ -1,27 +1,79 @@
-use rand::Rng;
+use crate::database::{Database, RomInfo};
+use crate::error::{Chip8Error, RomError};
+use crate::instruction::{decode, Instruction};
+use crate::quirks::Quirks;
+use crate::sha1;
+use crate::trace::Tracer;
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg32;
 use std::fs;
+use std::io::Read;
+
+mod state;
+
+// the chip-8 screen size in pixels
+pub const WIDTH: usize = 64;
+pub const HEIGHT: usize = 32;
+// the SUPER-CHIP high resolution mode doubles both dimensions
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+// XO-CHIP extends the memory to the whole 16 bits address space
+pub const MEMORY_SIZE: usize = 0x10000;
 
 pub struct Chip8 {
-    pub opcode: usize,
-    memory: [usize; 4096],
+    opcode: usize,
+    memory: Vec<usize>,
     reg: [usize; 16],
     pc: usize,
     index: usize,
-    pub delay_timer: usize,
-    pub sound_timer: usize,
+    delay_timer: usize,
+    sound_timer: usize,
+    // number of 60Hz timer ticks since the start
+    ticks: u64,
     stack: [usize; 16],
     sp: usize,
-    pub key: [bool; 16],
-    pub gfx: [bool; 64 * 32],
+    key: [bool; 16],
+    // big enough for the high resolution, in low resolution
+    // only the first WIDTH * HEIGHT pixels are used. Each pixel
+    // has one bit per XO-CHIP bitplane
+    gfx: [u8; HIRES_WIDTH * HIRES_HEIGHT],
+    hires: bool,
+    // bitplanes affected by drawing, selected by FN01
+    planes: u8,
+    // the XO-CHIP audio, a 1-bit pattern of 128 samples loaded by
+    // F002 and played at a rate set by FX3A
+    audio_pattern: Option<[u8; 16]>,
+    pitch: usize,
+    // the SUPER-CHIP RPL user flags, saved by FX75
+    rpl: [usize; 16],
+    // set by 00FD, the rom asked to stop the interpreter
+    halted: bool,
     // internal flag
-    pub update_screen: bool,
+    update_screen: bool,
+    // the key pressed during a FX0A, waiting to be released
+    key_wait: Option<usize>,
+    // set while a FX0A is waiting for a key
+    waiting_key: bool,
+    quirks: Quirks,
+    // used by CXNN, it can be seeded to make runs reproducible. Unlike
+    // StdRng its algorithm is fixed, so a seed gives the same numbers
+    // with any version of rand
+    rng: Pcg32,
+    // SHA-1 of the loaded rom, save states are bound to it
+    rom_hash: [u8; 20],
+    // logs the executed instructions when attached
+    tracer: Option<Tracer>,
 }
 
 impl Chip8 {
     // fonts are loaded starting at this address
     const FONT_ADDR: usize = 0x050;
+    // and the SUPER-CHIP big font right after it
+    const BIG_FONT_ADDR: usize = 0x0A0;
     // the pc starts at this address
-    const START_ADDR: usize = 0x200;
+    pub const START_ADDR: usize = 0x200;
+    // the biggest rom that fits between START_ADDR and the end of memory
+    pub const MAX_ROM_SIZE: usize = MEMORY_SIZE - Chip8::START_ADDR;
     // the size of a opcode, used in some contexts
     const OPCODE_SIZE: usize = 2;
     // each member of the font is drawed line by line
@@ -43,120 +95,466 @@ impl Chip8 {
         0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
         0xF0, 0x80, 0xF0, 0x80, 0x80, // F
     ];
+    // the big font used by FX30, 8x10 pixels per member
+    const BIG_FONT: [usize; 160] = [
+        0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+        0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+        0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+        0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+        0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+        0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+        0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+        0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+        0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+        0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+        0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+        0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+        0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+        0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+        0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+        0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+    ];
 
     pub fn init() -> Self {
-        //set all memory to 0's
-        let mut memory = [0; 4096];
-
-        // load the font into memory
-        let mut k = 0;
-        for i in Chip8::FONT.iter() {
-            memory[Chip8::FONT_ADDR + k] = *i;
-            k += 1;
-        }
-        // initiliazes the struct
-        Self {
+        Chip8::with_quirks(Quirks::default())
+    }
+    // creates the machine emulating the given interpreter behaviour
+    pub fn with_quirks(quirks: Quirks) -> Self {
+        // initiliazes the struct, with all memory set to 0's
+        let mut chip8 = Self {
             opcode: 0,
-            memory,
+            memory: vec![0; MEMORY_SIZE],
             reg: [0; 16],
             pc: Chip8::START_ADDR,
             index: 0,
             delay_timer: 0,
             sound_timer: 0,
+            ticks: 0,
             stack: [0; 16],
             sp: 0,
             key: [false; 16],
-            gfx: [false; 64 * 32],
+            gfx: [0; HIRES_WIDTH * HIRES_HEIGHT],
+            hires: false,
+            planes: 1,
+            audio_pattern: None,
+            pitch: 64,
+            rpl: [0; 16],
+            halted: false,
             update_screen: false,
-        }
+            key_wait: None,
+            waiting_key: false,
+            quirks,
+            rng: Pcg32::from_entropy(),
+            rom_hash: sha1::digest(&[]),
+            tracer: None,
+        };
+        // load the fonts into memory
+        chip8.load_font();
+        chip8
     }
-    // loads the font starting from a defined offset
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+    // attaches a tracer, it's called for every executed instruction
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = Some(tracer);
+    }
+    pub fn take_tracer(&mut self) -> Option<Tracer> {
+        self.tracer.take()
+    }
+    // makes the random numbers of CXNN always the same
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = Pcg32::seed_from_u64(seed);
+    }
+    // loads the fonts starting from a defined offset
     pub fn load_font(&mut self) {
-        for i in 0..Chip8::FONT.len() {
-            self.memory[i + Chip8::FONT_ADDR] = Chip8::FONT[i];
+        for (i, byte) in Chip8::FONT.iter().enumerate() {
+            self.memory[i + Chip8::FONT_ADDR] = *byte;
+        }
+        for (i, byte) in Chip8::BIG_FONT.iter().enumerate() {
+            self.memory[i + Chip8::BIG_FONT_ADDR] = *byte;
         }
     }
     // loads the rom(if possible) starting from a defined offset
-    pub fn load_rom(&mut self, path: &str) {
-        let rom = fs::read(path).expect("Unable to read file");
+    pub fn load_rom(&mut self, path: &str) -> Result<(), RomError> {
+        let rom = fs::read(path)?;
+        self.load_rom_bytes(&rom)
+    }
+    // loads the rom and, when the database knows it, switches to the
+    // quirks it was written for. What else is known is returned
+    pub fn load_rom_with(&mut self, path: &str, database: &Database) -> Result<Option<RomInfo>, RomError> {
+        self.load_rom(path)?;
+        let info = database.lookup(&self.rom_hash).cloned();
+        if let Some(info) = &info {
+            self.quirks = info.quirks;
+        }
+        Ok(info)
+    }
+    // loads a rom already in memory, e.g. one embedded in the binary
+    pub fn load_rom_bytes(&mut self, rom: &[u8]) -> Result<(), RomError> {
+        if rom.len() > Chip8::MAX_ROM_SIZE {
+            return Err(RomError::TooLarge {
+                size: rom.len(),
+                max: Chip8::MAX_ROM_SIZE,
+            });
+        }
+        for (i, byte) in rom.iter().enumerate() {
+            self.memory[i + Chip8::START_ADDR] = (*byte).into();
+        }
+        self.rom_hash = sha1::digest(rom);
+        Ok(())
+    }
+    // loads a rom from any reader, like stdin or a file inside an archive
+    pub fn load_rom_reader<R: Read>(&mut self, reader: R) -> Result<(), RomError> {
+        let mut rom = Vec::new();
+        // reads one byte past the limit so oversized roms are detected
+        // without reading the whole stream
+        reader
+            .take(Chip8::MAX_ROM_SIZE as u64 + 1)
+            .read_to_end(&mut rom)?;
+        self.load_rom_bytes(&rom)
+    }
+
+    // executes a single instruction
+    pub fn step(&mut self) -> Result<(), Chip8Error> {
+        if self.halted {
+            return Ok(());
+        }
+        let result = self.get_opcode().and_then(|_| {
+            if let Some(mut tracer) = self.tracer.take() {
+                tracer.record(self);
+                self.tracer = Some(tracer);
+            }
+            self.decode_opcode()
+        });
+        // the last instructions are what explains an error
+        if result.is_err() {
+            if let Some(tracer) = &mut self.tracer {
+                tracer.dump();
+            }
+        }
+        result
+    }
+    // executes the given number of instructions, meant to be
+    // called once per frame by the frontend
+    pub fn run_frame(&mut self, cycles: usize) -> Result<(), Chip8Error> {
+        self.run_frame_until(cycles, |_| false)
+    }
+    // the same, stop is asked after each instruction whether
+    // to end the frame there
+    pub fn run_frame_until(&mut self, cycles: usize, mut stop: impl FnMut(&Chip8) -> bool) -> Result<(), Chip8Error> {
+        for _ in 0..cycles {
+            self.step()?;
+            if stop(self) || self.ends_frame() {
+                break;
+            }
+        }
+        Ok(())
+    }
+    // with the display wait quirk a draw ends the frame,
+    // as it would wait for the vertical blank
+    pub(crate) fn ends_frame(&self) -> bool {
+        self.quirks.display_wait && self.opcode & 0xF000 == 0xD000
+    }
 
-        for i in 0..rom.len() {
-            self.memory[i + Chip8::START_ADDR] = rom[i].into();
+    // the screen, row by row. Each pixel is the bitmask of the lit
+    // bitplanes, so 0 is off and 1 is on outside of XO-CHIP roms.
+    // Its size depends on the current resolution
+    pub fn framebuffer(&self) -> &[u8] {
+        &self.gfx[..self.width() * self.height()]
+    }
+    // the current screen width, 64 or 128 in high resolution
+    pub fn width(&self) -> usize {
+        if self.hires {
+            HIRES_WIDTH
+        } else {
+            WIDTH
+        }
+    }
+    // the current screen height, 32 or 64 in high resolution
+    pub fn height(&self) -> usize {
+        if self.hires {
+            HIRES_HEIGHT
+        } else {
+            HEIGHT
+        }
+    }
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+    // true after the rom executed a 00FD, nothing else is executed
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+    // returns if the screen changed since the last call, clearing the flag
+    pub fn take_screen_update(&mut self) -> bool {
+        let updated = self.update_screen;
+        self.update_screen = false;
+        updated
+    }
+    // sets the state of one of the 16 keys of the keypad
+    pub fn set_key(&mut self, key: usize, pressed: bool) {
+        self.key[key] = pressed;
+    }
+    pub fn is_key_pressed(&self, key: usize) -> bool {
+        self.key[key]
+    }
+    // true while a FX0A is blocking the execution waiting for a key
+    pub fn is_waiting_key(&self) -> bool {
+        self.waiting_key
+    }
+    pub fn delay_timer(&self) -> usize {
+        self.delay_timer
+    }
+    pub fn set_delay_timer(&mut self, value: usize) {
+        self.delay_timer = value & 0xFF;
+    }
+    pub fn sound_timer(&self) -> usize {
+        self.sound_timer
+    }
+    pub fn set_sound_timer(&mut self, value: usize) {
+        self.sound_timer = value & 0xFF;
+    }
+    // the XO-CHIP audio pattern, if the rom loaded one
+    pub fn audio_pattern(&self) -> Option<&[u8; 16]> {
+        self.audio_pattern.as_ref()
+    }
+    // the rate in Hz the audio pattern bits are played at
+    pub fn audio_rate(&self) -> f32 {
+        4000.0 * 2f32.powf((self.pitch as f32 - 64.0) / 48.0)
+    }
+    // decreases the delay and sound timers, it has to be called exactly
+    // 60 times per emulated second, independently of the cpu speed
+    pub fn tick_timers(&mut self) {
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
         }
+        self.ticks += 1;
+    }
+    // how many times the timers ticked, i.e. the emulated time in 1/60s
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+    pub fn set_pc(&mut self, pc: usize) {
+        self.pc = pc & 0xFFFF;
+    }
+    pub fn opcode(&self) -> usize {
+        self.opcode
+    }
+    // V0 to VF
+    pub fn registers(&self) -> &[usize; 16] {
+        &self.reg
+    }
+    pub fn set_register(&mut self, x: usize, value: usize) {
+        self.reg[x] = value & 0xFF;
+    }
+    // the I register
+    pub fn index(&self) -> usize {
+        self.index
+    }
+    pub fn set_index(&mut self, value: usize) {
+        self.index = value & 0xFFFF;
+    }
+    // the return addresses, only the first sp() are in use
+    pub fn stack(&self) -> &[usize; 16] {
+        &self.stack
+    }
+    pub fn sp(&self) -> usize {
+        self.sp
+    }
+    // the stack has 16 levels, sp can't go past them
+    pub fn set_sp(&mut self, sp: usize) {
+        self.sp = sp.min(self.stack.len());
+    }
+    pub fn memory(&self) -> &[usize] {
+        &self.memory
+    }
+    pub fn set_memory(&mut self, addr: usize, value: u8) {
+        self.memory[addr] = value as usize;
+    }
+    // SHA-1 of the loaded rom
+    pub fn rom_hash(&self) -> &[u8; 20] {
+        &self.rom_hash
     }
 
+    // checks if the len bytes starting at addr are inside the memory
+    fn check_mem(&self, addr: usize, len: usize) -> Result<(), Chip8Error> {
+        if addr + len > self.memory.len() {
+            return Err(Chip8Error::MemoryOutOfBounds {
+                pc: self.pc,
+                opcode: self.opcode,
+                addr: addr.max(self.memory.len()),
+            });
+        }
+        Ok(())
+    }
+    // skips the next instruction, which is 4 bytes long if it's a F000 NNNN
+    fn skip(&mut self) {
+        let next = self.pc + Chip8::OPCODE_SIZE;
+        let long = next + 1 < self.memory.len()
+            && self.memory[next] == 0xF0
+            && self.memory[next + 1] == 0x00;
+        self.pc += if long { 2 } else { 1 } * Chip8::OPCODE_SIZE;
+    }
     // reads the opcode pointed by PC
-    pub fn get_opcode(&mut self) {
+    fn get_opcode(&mut self) -> Result<(), Chip8Error> {
+        self.check_mem(self.pc, Chip8::OPCODE_SIZE)?;
         let high = self.memory[self.pc];
         let low = self.memory[self.pc + 1];
         self.opcode = ((high) << 8) | low;
-    }
-    // decodes the opcode and calls the correct function
-    pub fn decode_opcode(&mut self) {
-        let opcode = self.opcode;
-        //println!("opcode: {:x}", opcode);
-        let cod1 = (opcode & 0xF000) >> 12;
-        let cod2 = (opcode & 0x0F00) >> 8;
-        let cod3 = (opcode & 0x00F0) >> 4;
-        let cod4 = opcode & 0x000F;
-        let nnn = opcode & 0x0FFF;
-        let nn = opcode & 0x00FF;
-        let n = opcode & 0x000F;
-        let vx = cod2;
-        let vy = cod3;
-
-        match (cod1, cod2, cod3, cod4) {
-            (0, 0, 0xE, 0) => self.clear_scr(),
-            (0, 0, 0xE, 0xE) => self.ret_from_sub(),
-            (1, _, _, _) => self.goto(nnn),
-            (2, _, _, _) => self.call(nnn),
-            (3, _, _, _) => self.ieq_const(vx, nn),
-            (4, _, _, _) => self.neq_const(vx, nn),
-            (5, _, _, 0) => self.ieq(vx, vy),
-            (6, _, _, _) => self.set_vx_const(vx, nn),
-            (7, _, _, _) => self.adds_const(vx, nn),
-            (8, _, _, 0) => self.set_vx_vy(vx, vy),
-            (8, _, _, 1) => self.set_or_vx_vy(vx, vy),
-            (8, _, _, 2) => self.set_and_vx_vy(vx, vy),
-            (8, _, _, 3) => self.set_xor_vx_vy(vx, vy),
-            (8, _, _, 4) => self.adds_vx_vy(vx, vy),
-            (8, _, _, 5) => self.subs_vx_vy(vx, vy),
-            (8, _, _, 6) => self.shift_r1(vx),
-            (8, _, _, 7) => self.subs_vy_vx(vx, vy),
-            (8, _, _, 0xE) => self.shift_l1(vx),
-            (9, _, _, 0) => self.neq(vx, vy),
-            (0xA, _, _, _) => self.set_i(nnn),
-            (0xB, _, _, _) => self.jump_v0(nnn),
-            (0xC, _, _, _) => self.random(vx, nn),
-            (0xD, _, _, _) => self.draw(vx, vy, n),
-            (0xE, _, 9, 0xE) => self.ieq_key(vx),
-            (0xE, _, 0xA, 1) => self.neq_key(vx),
-            (0xF, _, 0, 7) => self.get_delay(vx),
-            (0xF, _, 0, 0xA) => self.get_key(vx),
-            (0xF, _, 1, 5) => self.set_delay(vx),
-            (0xF, _, 1, 8) => self.set_sound(vx),
-            (0xF, _, 1, 0xE) => self.add_i_vx(vx),
-            (0xF, _, 2, 9) => self.set_i_sprite(vx),
-            (0xF, _, 3, 3) => self.set_bcd(vx),
-            (0xF, _, 5, 5) => self.store_regs_mem(vx),
-            (0xF, _, 6, 5) => self.load_regs_mem(vx),
-            (_, _, _, _) => panic!("opcode {:?} not found", self.opcode),
+        Ok(())
+    }
+    // decodes the opcode and executes it
+    fn decode_opcode(&mut self) -> Result<(), Chip8Error> {
+        let instruction = decode(self.opcode as u16).map_err(|_| Chip8Error::UnknownOpcode {
+            pc: self.pc,
+            opcode: self.opcode,
+        })?;
+        self.execute(&instruction)
+    }
+    // executes an instruction as if it was the one at pc, which
+    // then moves to the next instruction (unless it's a jump)
+    pub fn execute(&mut self, instruction: &Instruction) -> Result<(), Chip8Error> {
+        use Instruction::*;
+        match *instruction {
+            ScrollDown(n) => self.scroll_down(n),
+            ScrollUp(n) => self.scroll_up(n),
+            Clear => self.clear_scr(),
+            Return => self.ret_from_sub()?,
+            ScrollRight => self.scroll_right(),
+            ScrollLeft => self.scroll_left(),
+            Exit => self.exit(),
+            Lores => self.set_hires(false),
+            Hires => self.set_hires(true),
+            Jump(nnn) => self.goto(nnn),
+            Call(nnn) => self.call(nnn)?,
+            SkipEqConst { x, nn } => self.ieq_const(x, nn),
+            SkipNeConst { x, nn } => self.neq_const(x, nn),
+            SkipEq { x, y } => self.ieq(x, y),
+            SaveRange { x, y } => self.store_range_mem(x, y)?,
+            LoadRange { x, y } => self.load_range_mem(x, y)?,
+            SetConst { x, nn } => self.set_vx_const(x, nn),
+            AddConst { x, nn } => self.adds_const(x, nn),
+            Set { x, y } => self.set_vx_vy(x, y),
+            Or { x, y } => self.set_or_vx_vy(x, y),
+            And { x, y } => self.set_and_vx_vy(x, y),
+            Xor { x, y } => self.set_xor_vx_vy(x, y),
+            Add { x, y } => self.adds_vx_vy(x, y),
+            Sub { x, y } => self.subs_vx_vy(x, y),
+            ShiftRight { x, y } => self.shift_r1(x, y),
+            SubN { x, y } => self.subs_vy_vx(x, y),
+            ShiftLeft { x, y } => self.shift_l1(x, y),
+            SkipNe { x, y } => self.neq(x, y),
+            SetI(nnn) => self.set_i(nnn),
+            JumpV0(nnn) => self.jump_v0(nnn),
+            Random { x, nn } => self.random(x, nn),
+            Draw { x, y, n } => self.draw(x, y, n)?,
+            SkipKey(x) => self.ieq_key(x)?,
+            SkipNotKey(x) => self.neq_key(x)?,
+            LongI => self.set_i_long()?,
+            Planes(n) => self.select_planes(n),
+            Audio => self.load_audio()?,
+            GetDelay(x) => self.get_delay(x),
+            WaitKey(x) => self.get_key(x),
+            SetDelay(x) => self.set_delay(x),
+            SetSound(x) => self.set_sound(x),
+            AddI(x) => self.add_i_vx(x),
+            Font(x) => self.set_i_sprite(x),
+            BigFont(x) => self.set_i_big_sprite(x),
+            Bcd(x) => self.set_bcd(x)?,
+            Pitch(x) => self.set_pitch(x),
+            Store(x) => self.store_regs_mem(x)?,
+            Load(x) => self.load_regs_mem(x)?,
+            StoreFlags(x) => self.store_regs_rpl(x),
+            LoadFlags(x) => self.load_regs_rpl(x),
         };
-        if cod1 != 1 && cod1 != 2 && cod1 != 0xB {
+        // FX0A stays on itself while it waits
+        if !matches!(instruction, Jump(_) | Call(_) | JumpV0(_)) && !self.waiting_key {
             self.pc += Chip8::OPCODE_SIZE;
         }
+        Ok(())
+    }
+    // moves the selected bitplanes by dx, dy pixels,
+    // filling the uncovered area with unlit pixels
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = (self.width() as isize, self.height() as isize);
+        let old = self.gfx;
+        for y in 0..height {
+            for x in 0..width {
+                let (sx, sy) = (x - dx, y - dy);
+                let src = if sx >= 0 && sx < width && sy >= 0 && sy < height {
+                    old[(sy * width + sx) as usize]
+                } else {
+                    0
+                };
+                let pixel = &mut self.gfx[(y * width + x) as usize];
+                *pixel = (*pixel & !self.planes) | (src & self.planes);
+            }
+        }
+        self.update_screen = true;
+    }
+    // 00CN
+    // scrolls the screen down by N lines
+    fn scroll_down(&mut self, n: usize) {
+        self.scroll(0, n as isize);
+    }
+    // 00DN
+    // scrolls the screen up by N lines
+    fn scroll_up(&mut self, n: usize) {
+        self.scroll(0, -(n as isize));
     }
     // 00E0
-    // set the screen to black
+    // set the selected bitplanes to black
     fn clear_scr(&mut self) {
-        self.gfx = [false; 64 * 32];
+        for pixel in self.gfx.iter_mut() {
+            *pixel &= !self.planes;
+        }
         self.update_screen = true;
     }
     // 00EE
     // return from function
-    fn ret_from_sub(&mut self) {
+    fn ret_from_sub(&mut self) -> Result<(), Chip8Error> {
+        if self.sp == 0 {
+            return Err(Chip8Error::StackUnderflow {
+                pc: self.pc,
+                opcode: self.opcode,
+            });
+        }
         self.sp -= 1;
         self.pc = self.stack[self.sp];
+        Ok(())
+    }
+    // 00FB
+    // scrolls the screen right by 4 pixels
+    fn scroll_right(&mut self) {
+        self.scroll(4, 0);
+    }
+    // 00FC
+    // scrolls the screen left by 4 pixels
+    fn scroll_left(&mut self) {
+        self.scroll(-4, 0);
+    }
+    // 00FD
+    // stops the interpreter
+    fn exit(&mut self) {
+        self.halted = true;
+    }
+    // 00FE/00FF
+    // switches between the low and high resolution, which
+    // clears the screen as the pixels change their size
+    fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.gfx = [0; HIRES_WIDTH * HIRES_HEIGHT];
+        self.update_screen = true;
     }
     // 1NNN
     // unconditional jump
@@ -165,30 +563,37 @@ impl Chip8 {
     }
     // 2NNN
     // function calling
-    fn call(&mut self, nnn: usize) {
+    fn call(&mut self, nnn: usize) -> Result<(), Chip8Error> {
+        if self.sp == self.stack.len() {
+            return Err(Chip8Error::StackOverflow {
+                pc: self.pc,
+                opcode: self.opcode,
+            });
+        }
         self.stack[self.sp] = self.pc;
         self.sp += 1;
         self.pc = nnn;
+        Ok(())
     }
     // 3XNN
     // if VX is equal to NN, skip the next instruction
     fn ieq_const(&mut self, vx: usize, nn: usize) {
         if self.reg[vx] == nn {
-            self.pc += Chip8::OPCODE_SIZE;
+            self.skip();
         }
     }
     // 4XNN
     // if VX isn't equal to NN, skip the next instruction
     fn neq_const(&mut self, vx: usize, nn: usize) {
         if self.reg[vx] != nn {
-            self.pc += Chip8::OPCODE_SIZE;
+            self.skip();
         }
     }
     // 5XY0
     // if VX is equal to VY, skip the next instruction
     fn ieq(&mut self, vx: usize, vy: usize) {
         if self.reg[vx] == self.reg[vy] {
-            self.pc += Chip8::OPCODE_SIZE;
+            self.skip();
         }
     }
     // 6XNN
@@ -209,73 +614,77 @@ impl Chip8 {
     // 8XY1
     // set VX to VX bitswise-OR VY
     fn set_or_vx_vy(&mut self, vx: usize, vy: usize) {
-        self.reg[vx] = self.reg[vx] | self.reg[vy];
+        self.reg[vx] |= self.reg[vy];
+        self.vf_reset();
     }
     // 8XY2
     // set VX to VX bitswise-AND VY
     fn set_and_vx_vy(&mut self, vx: usize, vy: usize) {
-        self.reg[vx] = self.reg[vx] & self.reg[vy];
+        self.reg[vx] &= self.reg[vy];
+        self.vf_reset();
     }
     // 8XY3
     // set VX to VX bitswise-XOR VY
     fn set_xor_vx_vy(&mut self, vx: usize, vy: usize) {
-        self.reg[vx] = self.reg[vx] ^ self.reg[vy];
+        self.reg[vx] ^= self.reg[vy];
+        self.vf_reset();
     }
-    // 8XY4
-    // set VX to VX added to VY and,
-    // if needed, setting VF to the carry flag
-    fn adds_vx_vy(&mut self, vx: usize, vy: usize) {
-        if self.reg[vx] + self.reg[vy] > 0xFF {
-            self.reg[0xF] = 1;
-        } else {
+    // the logical operations of the COSMAC VIP left VF zeroed
+    fn vf_reset(&mut self) {
+        if self.quirks.vf_reset {
             self.reg[0xF] = 0;
         }
-        self.reg[vx] = (self.reg[vx] + self.reg[vy]) % 0x100;
+    }
+    // 8XY4
+    // set VX to VX added to VY and VF to the carry flag.
+    // VF is written last, so it holds the flag even when it's VX
+    fn adds_vx_vy(&mut self, vx: usize, vy: usize) {
+        let sum = self.reg[vx] + self.reg[vy];
+        self.reg[vx] = sum & 0xFF;
+        self.reg[0xF] = (sum > 0xFF) as usize;
     }
     // 8XY5
-    // set VX to VY subtracted from VX and, if needed,
-    // setting VF to the borrow flag
+    // set VX to VY subtracted from VX and VF to 1
+    // when there's no borrow (VX >= VY)
     fn subs_vx_vy(&mut self, vx: usize, vy: usize) {
-        if self.reg[vx] > self.reg[vy] {
-            self.reg[0xF] = 1;
-        } else {
-            self.reg[0xF] = 0;
-        }
-        self.reg[vx] = self.reg[vx].wrapping_sub(self.reg[vy]);
+        let (x, y) = (self.reg[vx], self.reg[vy]);
+        self.reg[vx] = x.wrapping_sub(y) & 0xFF;
+        self.reg[0xF] = (x >= y) as usize;
     }
     // 8XY6
-    // stores the least significant bit of VX in VF and
-    // then shifts VX to the right by 1
-    fn shift_r1(&mut self, vx: usize) {
+    // stores the least significant bit of VY in VF and then sets
+    // VX to VY shifted to the right by 1 (VX is used instead
+    // of VY with the shift quirk)
+    fn shift_r1(&mut self, vx: usize, vy: usize) {
+        let value = if self.quirks.shift { self.reg[vx] } else { self.reg[vy] };
         // bitmask to get the least sig. bit
-        self.reg[0xF] = self.reg[vx] & 0x1;
-        self.reg[vx] = self.reg[vx] >> 1;
+        self.reg[vx] = value >> 1;
+        self.reg[0xF] = value & 0x1;
     }
     // 8XY7
-    // set VX to VX subtracted from VY and, if needed,
-    // setting VF to the borrow flag
+    // set VX to VX subtracted from VY and VF to 1
+    // when there's no borrow (VY >= VX)
     fn subs_vy_vx(&mut self, vx: usize, vy: usize) {
-        if self.reg[vy] > self.reg[vx] {
-            self.reg[0xF] = 1;
-        } else {
-            self.reg[0xF] = 0;
-        }
-        self.reg[vx] = self.reg[vy].wrapping_sub(self.reg[vx]);
+        let (x, y) = (self.reg[vx], self.reg[vy]);
+        self.reg[vx] = y.wrapping_sub(x) & 0xFF;
+        self.reg[0xF] = (y >= x) as usize;
     }
     // 8XYE
-    // stores the most significant bit of VX in VF and
-    // then shifts VX to the left by 1
-    fn shift_l1(&mut self, vx: usize) {
+    // stores the most significant bit of VY in VF and then sets
+    // VX to VY shifted to the left by 1 (VX is used instead
+    // of VY with the shift quirk)
+    fn shift_l1(&mut self, vx: usize, vy: usize) {
+        let value = if self.quirks.shift { self.reg[vx] } else { self.reg[vy] };
+        self.reg[vx] = (value << 1) & 0xFF;
         // uses a bitmask to get the most sig. bit
         // then pushing it to the end
-        self.reg[0xF] = (self.reg[vx] & 0x80) >> 7;
-        self.reg[vx] = self.reg[vx] << 1;
+        self.reg[0xF] = (value & 0x80) >> 7;
     }
     // 9XY0
     // if VX is not equal to VY, skip the next instruction
     fn neq(&mut self, vx: usize, vy: usize) {
         if self.reg[vx] != self.reg[vy] {
-            self.pc += Chip8::OPCODE_SIZE;
+            self.skip();
         }
     }
     // ANNN
@@ -284,50 +693,114 @@ impl Chip8 {
         self.index = nnn;
     }
     // BNNN
-    // sets pc to V0 + NNN
+    // sets pc to V0 + NNN, or VX + NNN with the jump quirk
+    // (BXNN, X being the highest nibble of NNN)
     fn jump_v0(&mut self, nnn: usize) {
-        self.pc = self.reg[0] + nnn;
+        let reg = if self.quirks.jump { nnn >> 8 } else { 0 };
+        self.pc = self.reg[reg] + nnn;
     }
     // CXNN
     // sets VX to rand() bitwise-and NNN
     fn random(&mut self, vx: usize, nn: usize) {
-        let mut rng = rand::thread_rng();
-        self.reg[vx] = rng.gen_range(0x00, 0xFE) & nn;
+        self.reg[vx] = self.rng.gen_range(0x00, 0x100) & nn;
     }
     // DXYN
     // draw a sprite at the coordinates VX, VY, with
-    // the data starting at I
-    fn draw(&mut self, vx: usize, vy: usize, n: usize) {
-        // iterates over the height/rows
-        let x = self.reg[vx];
-        let y = self.reg[vy];
+    // the data starting at I. DXY0 draws a 16x16 sprite
+    // made of 2 bytes per row. With both XO-CHIP bitplanes
+    // selected the data of the second plane follows the first
+    fn draw(&mut self, vx: usize, vy: usize, n: usize) -> Result<(), Chip8Error> {
+        let (rows, row_bytes) = if n == 0 { (16, 2) } else { (n, 1) };
+        let sprite_len = rows * row_bytes;
+        self.check_mem(self.index, sprite_len * self.planes.count_ones() as usize)?;
+        let (width, height) = (self.width(), self.height());
+        // iterates over the height/rows, the starting
+        // position always wraps around the screen
+        let x = self.reg[vx] % width;
+        let y = self.reg[vy] % height;
         self.reg[0xF] = 0;
-        for yline in 0..n {
-            let pixel = self.memory[self.index + yline];
-            // iterates collumn by collumn(fixed size of 8)
-            for xline in 0..8 {
-                if (pixel & (0x80 >> xline)) != 0 {
-                    if self.gfx[((x + xline)%64 + ((y + yline)%32 * 64))] == true {
-                        self.reg[0xF] = 1;
+        let mut addr = self.index;
+        let planes = self.planes;
+        for plane in [1u8, 2].iter().filter(|p| planes & **p != 0) {
+            for yline in 0..rows {
+                if self.quirks.clip && y + yline >= height {
+                    break;
+                }
+                let row = addr + yline * row_bytes;
+                let pixel = if row_bytes == 2 {
+                    (self.memory[row] << 8) | self.memory[row + 1]
+                } else {
+                    self.memory[row]
+                };
+                // iterates collumn by collumn(8 or 16 of them)
+                let bits = row_bytes * 8;
+                for xline in 0..bits {
+                    if self.quirks.clip && x + xline >= width {
+                        break;
+                    }
+                    if (pixel & (1 << (bits - 1 - xline))) != 0 {
+                        let pos = (x + xline) % width + ((y + yline) % height) * width;
+                        if self.gfx[pos] & plane != 0 {
+                            self.reg[0xF] = 1;
+                        }
+                        self.gfx[pos] ^= plane;
                     }
-                    self.gfx[((x + xline)%64 + ((y + yline)%32 * 64))] ^= true;
                 }
             }
+            addr += sprite_len;
         }
         self.update_screen = true;
+        Ok(())
+    }
+    // 5XY2
+    // stores VX to VY (in this order, so it may be backwards)
+    // starting at the address I, I isn't changed
+    fn store_range_mem(&mut self, vx: usize, vy: usize) -> Result<(), Chip8Error> {
+        let len = vx.abs_diff(vy) + 1;
+        self.check_mem(self.index, len)?;
+        for i in 0..len {
+            let reg = if vx > vy { vx - i } else { vx + i };
+            self.memory[self.index + i] = self.reg[reg];
+        }
+        Ok(())
+    }
+    // 5XY3
+    // loads VX to VY (in this order, so it may be backwards)
+    // starting at the address I, I isn't changed
+    fn load_range_mem(&mut self, vx: usize, vy: usize) -> Result<(), Chip8Error> {
+        let len = vx.abs_diff(vy) + 1;
+        self.check_mem(self.index, len)?;
+        for i in 0..len {
+            let reg = if vx > vy { vx - i } else { vx + i };
+            self.reg[reg] = self.memory[self.index + i];
+        }
+        Ok(())
     }
     // EX9E
     // if VX is equal to the key, skip the next instruction
-    fn ieq_key(&mut self, vx: usize) {
-        if self.key[self.reg[vx]] == true {
-            self.pc += Chip8::OPCODE_SIZE;
+    fn ieq_key(&mut self, vx: usize) -> Result<(), Chip8Error> {
+        if self.vx_key(vx)? {
+            self.skip();
         }
+        Ok(())
     }
     // EXA1
     // if VX is not equal to the key, skip the next instruction
-    fn neq_key(&mut self, vx: usize) {
-        if self.key[self.reg[vx]] == false {
-            self.pc += Chip8::OPCODE_SIZE;
+    fn neq_key(&mut self, vx: usize) -> Result<(), Chip8Error> {
+        if !self.vx_key(vx)? {
+            self.skip();
+        }
+        Ok(())
+    }
+    // state of the key stored in VX
+    fn vx_key(&self, vx: usize) -> Result<bool, Chip8Error> {
+        match self.key.get(self.reg[vx]) {
+            Some(pressed) => Ok(*pressed),
+            None => Err(Chip8Error::InvalidKey {
+                pc: self.pc,
+                opcode: self.opcode,
+                key: self.reg[vx],
+            }),
         }
     }
     // FX07
@@ -335,19 +808,49 @@ impl Chip8 {
     fn get_delay(&mut self, vx: usize) {
         self.reg[vx] = self.delay_timer;
     }
+    // F000 NNNN
+    // sets I to the 16 bits address NNNN that follows the opcode
+    fn set_i_long(&mut self) -> Result<(), Chip8Error> {
+        let addr = self.pc + Chip8::OPCODE_SIZE;
+        self.check_mem(addr, 2)?;
+        self.index = (self.memory[addr] << 8) | self.memory[addr + 1];
+        // skips the address, the opcode itself is skipped as usual
+        self.pc += Chip8::OPCODE_SIZE;
+        Ok(())
+    }
+    // FN01
+    // selects the bitplanes (N being 0 to 3) used by drawing,
+    // clearing and scrolling
+    fn select_planes(&mut self, n: usize) {
+        self.planes = (n & 0x3) as u8;
+    }
+    // F002
+    // loads the 16 bytes starting at I into the audio pattern
+    fn load_audio(&mut self) -> Result<(), Chip8Error> {
+        self.check_mem(self.index, 16)?;
+        let mut pattern = [0; 16];
+        for (i, byte) in pattern.iter_mut().enumerate() {
+            *byte = self.memory[self.index + i] as u8;
+        }
+        self.audio_pattern = Some(pattern);
+        Ok(())
+    }
     // FX0A
-    // waits for a key to get pressed
+    // waits for a key to get pressed and then released, like the
+    // COSMAC VIP did. The keys only change between steps, so instead
+    // of blocking the instruction is executed again until it's done
     fn get_key(&mut self, vx: usize) {
-        let mut pressed = false;
-        while !pressed {
-            for i in 0..self.key.len() {
-                if self.key[i] == true {
-                    pressed = true;
-                    self.reg[vx] = i;
-                    break;
-                }
+        match self.key_wait {
+            None => self.key_wait = self.key.iter().position(|&pressed| pressed),
+            Some(k) if !self.key[k] => {
+                self.reg[vx] = k;
+                self.key_wait = None;
+                self.waiting_key = false;
+                return;
             }
+            Some(_) => (),
         }
+        self.waiting_key = true;
     }
     // FX15
     // set the delay timer to the value of VX
@@ -362,33 +865,67 @@ impl Chip8 {
     // FX1E
     // sets I to VX added to I
     fn add_i_vx(&mut self, vx: usize) {
-        self.index += self.reg[vx];
+        self.index = (self.index + self.reg[vx]) & 0xFFFF;
     }
     // FX29
     // sets I to the spr_addr added to VX
     fn set_i_sprite(&mut self, vx: usize) {
         self.index = Chip8::FONT_ADDR + self.reg[vx] * 5;
     }
+    // FX30
+    // sets I to the big font sprite of the digit in VX
+    fn set_i_big_sprite(&mut self, vx: usize) {
+        self.index = Chip8::BIG_FONT_ADDR + (self.reg[vx] & 0xF) * 10;
+    }
     // FX33
     // gets the BCD of VX and sets I to the hundreds place,
     // I + 1 to the tens, and I + 2 to the ones
-    fn set_bcd(&mut self, vx: usize) {
+    fn set_bcd(&mut self, vx: usize) -> Result<(), Chip8Error> {
+        self.check_mem(self.index, 3)?;
         self.memory[self.index] = self.reg[vx] / 100;
         self.memory[self.index + 1] = (self.reg[vx] % 100) / 10;
         self.memory[self.index + 2] = self.reg[vx] % 10;
+        Ok(())
+    }
+    // FX3A
+    // sets the audio pattern pitch to VX
+    fn set_pitch(&mut self, vx: usize) {
+        self.pitch = self.reg[vx];
     }
     // FX55
     // store the value from all registers starting at the address I
-    fn store_regs_mem(&mut self, vx: usize) {
+    fn store_regs_mem(&mut self, vx: usize) -> Result<(), Chip8Error> {
+        self.check_mem(self.index, vx + 1)?;
         for i in 0..vx + 1 {
             self.memory[self.index + i] = self.reg[i];
         }
+        self.increment_i_load_store(vx);
+        Ok(())
     }
     // FX65
     // loads values in all registers starting at the address I
-    fn load_regs_mem(&mut self, vx: usize) {
+    fn load_regs_mem(&mut self, vx: usize) -> Result<(), Chip8Error> {
+        self.check_mem(self.index, vx + 1)?;
         for i in 0..vx + 1 {
             self.reg[i] = self.memory[self.index + i];
         }
+        self.increment_i_load_store(vx);
+        Ok(())
+    }
+    // FX75
+    // stores V0 to VX in the RPL user flags
+    fn store_regs_rpl(&mut self, vx: usize) {
+        self.rpl[..=vx].copy_from_slice(&self.reg[..=vx]);
+    }
+    // FX85
+    // loads V0 to VX from the RPL user flags
+    fn load_regs_rpl(&mut self, vx: usize) {
+        self.reg[..=vx].copy_from_slice(&self.rpl[..=vx]);
+    }
+    // the COSMAC VIP left I pointing after the last register
+    fn increment_i_load_store(&mut self, vx: usize) {
+        if !self.quirks.load_store {
+            self.index = (self.index + vx + 1) & 0xFFFF;
+        }
     }
 }
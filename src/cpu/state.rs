@@ -0,0 +1,210 @@
+// save states: the whole machine written in a binary format.
+// Everything is little endian, after a header made of the magic
+// bytes, the format version and the SHA-1 of the rom. The keys
+// (they belong to the host) and the quirks (they're configuration)
+// aren't saved
+use super::{Chip8, HIRES_HEIGHT, HIRES_WIDTH, MEMORY_SIZE};
+use crate::error::StateError;
+
+const MAGIC: &[u8; 4] = b"C8ST";
+// has to be increased whenever the layout changes
+const VERSION: u16 = 1;
+
+struct Writer(Vec<u8>);
+
+// the machine and its setters keep the values in range, one that
+// still doesn't fit is truncated like the hardware register would,
+// saving must not fail
+fn fit(value: usize, bits: u32) -> usize {
+    value & ((1 << bits) - 1)
+}
+
+impl Writer {
+    fn u8(&mut self, value: usize) {
+        self.0.push(fit(value, 8) as u8);
+    }
+    fn u16(&mut self, value: usize) {
+        self.0.extend_from_slice(&(fit(value, 16) as u16).to_le_bytes());
+    }
+    fn u32(&mut self, value: usize) {
+        self.0.extend_from_slice(&(fit(value, 32) as u32).to_le_bytes());
+    }
+    fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+    fn bool(&mut self, value: bool) {
+        self.0.push(value as u8);
+    }
+    fn bytes(&mut self, values: &[usize]) {
+        self.0.extend(values.iter().map(|v| fit(*v, 8) as u8));
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or(StateError::Corrupted)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+    fn u8(&mut self) -> Result<usize, StateError> {
+        Ok(self.take(1)?[0] as usize)
+    }
+    fn u16(&mut self) -> Result<usize, StateError> {
+        let b = self.take(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]) as usize)
+    }
+    fn u32(&mut self) -> Result<usize, StateError> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+    }
+    fn u64(&mut self) -> Result<u64, StateError> {
+        let mut b = [0; 8];
+        b.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(b))
+    }
+    fn bool(&mut self) -> Result<bool, StateError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(StateError::Corrupted),
+        }
+    }
+    fn bytes(&mut self, values: &mut [usize]) -> Result<(), StateError> {
+        let bytes = self.take(values.len())?;
+        for (value, byte) in values.iter_mut().zip(bytes) {
+            *value = *byte as usize;
+        }
+        Ok(())
+    }
+}
+
+impl Chip8 {
+    // captures the whole state of the machine
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut w = Writer(Vec::with_capacity(MEMORY_SIZE + HIRES_WIDTH * HIRES_HEIGHT + 256));
+        w.0.extend_from_slice(MAGIC);
+        w.u16(VERSION as usize);
+        w.0.extend_from_slice(&self.rom_hash);
+
+        w.bytes(&self.memory);
+        w.bytes(&self.reg);
+        w.u16(self.opcode);
+        w.u32(self.pc);
+        w.u32(self.index);
+        w.u8(self.delay_timer);
+        w.u8(self.sound_timer);
+        w.u64(self.ticks);
+        for addr in self.stack.iter() {
+            w.u32(*addr);
+        }
+        w.u8(self.sp);
+        w.0.extend_from_slice(&self.gfx);
+        w.bool(self.hires);
+        w.u8(self.planes as usize);
+        match self.audio_pattern {
+            Some(pattern) => {
+                w.bool(true);
+                w.0.extend_from_slice(&pattern);
+            }
+            None => w.bool(false),
+        }
+        w.u8(self.pitch);
+        w.bytes(&self.rpl);
+        w.bool(self.halted);
+        // 0xFF when FX0A isn't waiting for a release
+        w.u8(self.key_wait.unwrap_or(0xFF));
+        w.bool(self.waiting_key);
+        w.0
+    }
+
+    // restores a state captured by save_state, as long as it was saved
+    // with the same rom loaded. On error the machine is left untouched
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let mut r = Reader { data, pos: 0 };
+        if r.take(MAGIC.len())? != MAGIC {
+            return Err(StateError::Corrupted);
+        }
+        let version = r.u16()? as u16;
+        if version != VERSION {
+            return Err(StateError::Version {
+                found: version,
+                expected: VERSION,
+            });
+        }
+        if r.take(self.rom_hash.len())? != self.rom_hash {
+            return Err(StateError::RomMismatch);
+        }
+
+        // reads into a copy so a damaged state doesn't leave
+        // the machine half restored
+        let mut memory = vec![0; MEMORY_SIZE];
+        r.bytes(&mut memory)?;
+        let mut reg = [0; 16];
+        r.bytes(&mut reg)?;
+        let opcode = r.u16()?;
+        let pc = r.u32()?;
+        let index = r.u32()?;
+        let delay_timer = r.u8()?;
+        let sound_timer = r.u8()?;
+        let ticks = r.u64()?;
+        let mut stack = [0; 16];
+        for addr in stack.iter_mut() {
+            *addr = r.u32()?;
+        }
+        let sp = r.u8()?;
+        let mut gfx = [0; HIRES_WIDTH * HIRES_HEIGHT];
+        gfx.copy_from_slice(r.take(HIRES_WIDTH * HIRES_HEIGHT)?);
+        let hires = r.bool()?;
+        let planes = r.u8()? as u8;
+        let audio_pattern = if r.bool()? {
+            let mut pattern = [0; 16];
+            pattern.copy_from_slice(r.take(16)?);
+            Some(pattern)
+        } else {
+            None
+        };
+        let pitch = r.u8()?;
+        let mut rpl = [0; 16];
+        r.bytes(&mut rpl)?;
+        let halted = r.bool()?;
+        let key_wait = match r.u8()? {
+            0xFF => None,
+            key if key < 16 => Some(key),
+            _ => return Err(StateError::Corrupted),
+        };
+        let waiting_key = r.bool()?;
+        if r.pos != data.len() || sp > stack.len() || planes > 3 {
+            return Err(StateError::Corrupted);
+        }
+
+        self.memory = memory;
+        self.reg = reg;
+        self.opcode = opcode;
+        self.pc = pc;
+        self.index = index;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.ticks = ticks;
+        self.stack = stack;
+        self.sp = sp;
+        self.gfx = gfx;
+        self.hires = hires;
+        self.planes = planes;
+        self.audio_pattern = audio_pattern;
+        self.pitch = pitch;
+        self.rpl = rpl;
+        self.halted = halted;
+        self.key_wait = key_wait;
+        self.waiting_key = waiting_key;
+        self.update_screen = true;
+        Ok(())
+    }
+}
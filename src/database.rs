@@ -0,0 +1,249 @@
+// what's known about the roms: their platform, and so the quirks and
+// speed they expect, their colours and which keys they use. The files
+// are the ones of the community database (github.com/chip-8/chip-8-database):
+//
+//     sha1-hashes.json   the SHA-1 of each rom -> its index in programs.json
+//     programs.json      the programs, each with one or more roms
+//     platforms.json     the quirks and the default speed of each platform
+//
+// A copy covering the roms in roms/ is built in, a directory with a full
+// version of the files can be loaded instead
+use crate::error::ConfigError;
+use crate::quirks::Quirks;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+const HASHES: &str = include_str!("../database/sha1-hashes.json");
+const PROGRAMS: &str = include_str!("../database/programs.json");
+const PLATFORMS: &str = include_str!("../database/platforms.json");
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RomInfo {
+    pub title: String,
+    // the id of the platform the rom was written for, like originalChip8
+    pub platform: String,
+    pub quirks: Quirks,
+    // instructions per frame
+    pub tickrate: Option<usize>,
+    // the colours of the pixels, by lit bitplanes like the palette of
+    // the frontend. Empty when the database doesn't say
+    pub colors: Vec<(u8, u8, u8)>,
+    // what the keys do, like up -> 5
+    pub keys: Vec<(String, usize)>,
+}
+
+// what the roms written for a platform expect
+#[derive(Debug, Clone, PartialEq)]
+pub struct Platform {
+    pub quirks: Quirks,
+    pub tickrate: Option<usize>,
+}
+
+// an empty one knows no rom
+#[derive(Default)]
+pub struct Database {
+    roms: HashMap<String, RomInfo>,
+    platforms: HashMap<String, Platform>,
+}
+
+impl Database {
+    pub fn builtin() -> Self {
+        Database::parse(HASHES, PROGRAMS, PLATFORMS).expect("the built-in database is valid")
+    }
+    // reads the three files of a directory
+    pub fn load(dir: &str) -> Result<Self, ConfigError> {
+        let read = |name: &str| {
+            let path = Path::new(dir).join(name);
+            fs::read_to_string(&path).map_err(|e| ConfigError {
+                file: path.to_string_lossy().into_owned(),
+                line: 0,
+                message: e.to_string(),
+            })
+        };
+        Database::parse(&read("sha1-hashes.json")?, &read("programs.json")?, &read("platforms.json")?)
+    }
+
+    pub fn parse(hashes: &str, programs: &str, platforms: &str) -> Result<Self, ConfigError> {
+        let hashes = parse_json(hashes, "sha1-hashes.json")?;
+        let programs = parse_json(programs, "programs.json")?;
+        let platforms = parse_json(platforms, "platforms.json")?;
+        let error = |file: &str, message: String| ConfigError {
+            file: file.to_string(),
+            line: 0,
+            message,
+        };
+
+        let mut by_id = HashMap::new();
+        for platform in platforms.as_array().map(Vec::as_slice).unwrap_or(&[]) {
+            let id = platform
+                .get("id")
+                .and_then(Value::as_str)
+                .ok_or_else(|| error("platforms.json", "a platform has no id".to_string()))?;
+            let quirks = quirks(platform.get("quirks").unwrap_or(&Value::Null));
+            let tickrate = platform.get("defaultTickrate").and_then(as_usize);
+            by_id.insert(id.to_string(), Platform { quirks, tickrate });
+        }
+
+        let programs = programs
+            .as_array()
+            .ok_or_else(|| error("programs.json", "the programs should be an array".to_string()))?;
+        let hashes = hashes
+            .as_object()
+            .ok_or_else(|| error("sha1-hashes.json", "the hashes should be an object".to_string()))?;
+        let mut roms = HashMap::new();
+        for (hash, index) in hashes {
+            let program = as_usize(index)
+                .and_then(|i| programs.get(i))
+                .ok_or_else(|| error("sha1-hashes.json", format!("{} has no program", hash)))?;
+            let rom = program
+                .get("roms")
+                .and_then(|roms| roms.get(hash))
+                .ok_or_else(|| error("programs.json", format!("no rom has the hash {}", hash)))?;
+            let title = program.get("title").and_then(Value::as_str).unwrap_or(hash);
+            let platform = rom
+                .get("platforms")
+                .and_then(Value::as_array)
+                .and_then(|platforms| platforms.first())
+                .and_then(Value::as_str)
+                .ok_or_else(|| error("programs.json", format!("{} has no platform", title)))?;
+            let known = by_id
+                .get(platform)
+                .ok_or_else(|| error("programs.json", format!("{} has an unknown platform {}", title, platform)))?;
+
+            // the rom can have its own quirks on top of the platform ones
+            let mut rom_quirks = known.quirks;
+            if let Some(overrides) = rom.get("quirkyPlatforms").and_then(|q| q.get(platform)) {
+                rom_quirks = apply_quirks(rom_quirks, overrides);
+            }
+            let colors = rom
+                .get("colors")
+                .and_then(|colors| colors.get("pixels"))
+                .and_then(Value::as_array)
+                .map(Vec::as_slice)
+                .unwrap_or(&[])
+                .iter()
+                .filter_map(|color| color.as_str().and_then(parse_color))
+                .collect();
+            let keys = rom
+                .get("keys")
+                .and_then(Value::as_object)
+                .map(|keys| {
+                    keys.iter()
+                        .filter_map(|(name, key)| Some((name.clone(), as_usize(key).filter(|k| *k <= 0xF)?)))
+                        .collect()
+                })
+                .unwrap_or_default();
+            let info = RomInfo {
+                title: title.to_string(),
+                platform: platform.to_string(),
+                quirks: rom_quirks,
+                tickrate: rom
+                    .get("tickrate")
+                    .and_then(as_usize)
+                    .or(known.tickrate),
+                colors,
+                keys,
+            };
+            roms.insert(hash.to_lowercase(), info);
+        }
+        Ok(Database { roms, platforms: by_id })
+    }
+
+    // the information about a rom, by the SHA-1 of its content
+    pub fn lookup(&self, hash: &[u8; 20]) -> Option<&RomInfo> {
+        self.roms.get(&crate::sha1::hex(hash))
+    }
+    // a platform by its id, like originalChip8, ignoring the case
+    pub fn platform(&self, id: &str) -> Option<&Platform> {
+        self.platforms
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(id))
+            .map(|(_, platform)| platform)
+    }
+    // the ids of the platforms, sorted
+    pub fn platform_ids(&self) -> Vec<&str> {
+        let mut ids: Vec<&str> = self.platforms.keys().map(String::as_str).collect();
+        ids.sort_unstable();
+        ids
+    }
+    pub fn len(&self) -> usize {
+        self.roms.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.roms.is_empty()
+    }
+}
+
+fn parse_json(source: &str, file: &str) -> Result<Value, ConfigError> {
+    serde_json::from_str(source).map_err(|e| {
+        // the line is already part of the ConfigError
+        let message = e.to_string();
+        let message = message.split(" at line ").next().unwrap_or_default();
+        ConfigError {
+            file: file.to_string(),
+            line: e.line(),
+            message: message.to_string(),
+        }
+    })
+}
+
+fn as_usize(value: &Value) -> Option<usize> {
+    value.as_u64().map(|n| n as usize)
+}
+
+// the database names the quirks after what the roms see, where Quirks
+// names them after what the interpreter does
+fn quirks(value: &Value) -> Quirks {
+    apply_quirks(
+        Quirks {
+            shift: false,
+            load_store: false,
+            jump: false,
+            clip: true,
+            vf_reset: false,
+            display_wait: false,
+        },
+        value,
+    )
+}
+
+fn apply_quirks(mut quirks: Quirks, value: &Value) -> Quirks {
+    let flag = |name| value.get(name).and_then(Value::as_bool);
+    if let Some(shift) = flag("shift") {
+        quirks.shift = shift;
+    }
+    // I incremented by X (CHIP-48) isn't emulated, like Quirks::CHIP_48
+    // it becomes the X + 1 of the COSMAC VIP: I still moves past the
+    // registers, only by one more
+    if let Some(unchanged) = flag("memoryLeaveIUnchanged") {
+        quirks.load_store = unchanged;
+    }
+    if flag("memoryIncrementByX") == Some(true) {
+        quirks.load_store = false;
+    }
+    if let Some(wrap) = flag("wrap") {
+        quirks.clip = !wrap;
+    }
+    if let Some(jump) = flag("jump") {
+        quirks.jump = jump;
+    }
+    if let Some(vblank) = flag("vblank") {
+        quirks.display_wait = vblank;
+    }
+    if let Some(logic) = flag("logic") {
+        quirks.vf_reset = logic;
+    }
+    quirks
+}
+
+// #RRGGBB
+pub fn parse_color(s: &str) -> Option<(u8, u8, u8)> {
+    let hex = s.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
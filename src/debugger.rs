@@ -0,0 +1,259 @@
+// a debugger wrapping the execution of a Chip8: it can pause, step one
+// instruction or one frame at a time and stop on breakpoints (on the pc)
+// and watchpoints (on a memory address or a register changing). The
+// commands the frontend reads from the terminal are parsed here too
+use crate::cpu::{Chip8, MEMORY_SIZE};
+use crate::disasm::{self, Syntax};
+use crate::error::Chip8Error;
+use std::collections::BTreeSet;
+use std::fmt;
+
+// something whose changes stop the execution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Watch {
+    Memory(usize),
+    // V0 to VF
+    Register(usize),
+    Index,
+}
+
+impl Watch {
+    fn read(self, chip8: &Chip8) -> usize {
+        match self {
+            Watch::Memory(addr) => chip8.memory()[addr],
+            Watch::Register(x) => chip8.registers()[x],
+            Watch::Index => chip8.index(),
+        }
+    }
+}
+
+impl fmt::Display for Watch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Watch::Memory(addr) => write!(f, "[{:#05X}]", addr),
+            Watch::Register(x) => write!(f, "v{:X}", x),
+            Watch::Index => write!(f, "i"),
+        }
+    }
+}
+
+// why the debugger paused the execution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    Breakpoint(usize),
+    Watchpoint { watch: Watch, old: usize, new: usize },
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stop::Breakpoint(pc) => write!(f, "breakpoint at {:#05X}", pc),
+            Stop::Watchpoint { watch, old, new } => {
+                write!(f, "{} changed from {:02X} to {:02X}", watch, old, new)
+            }
+        }
+    }
+}
+
+pub const HELP: &str = "debugger commands:
+  p            pause
+  c            continue
+  s            step one instruction
+  f            step one frame
+  b ADDR       add or remove a breakpoint
+  w ADDR|vX|i  add or remove a watchpoint
+  v            show the registers and the code around pc
+  h            this help";
+
+// a command typed in the terminal of the frontend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Pause,
+    Continue,
+    Step,
+    Frame,
+    Break(usize),
+    Watch(Watch),
+    View,
+    Help,
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["p"] => Ok(Command::Pause),
+            ["c"] => Ok(Command::Continue),
+            ["s"] => Ok(Command::Step),
+            ["f"] => Ok(Command::Frame),
+            ["b", addr] => parse_num(addr).map(Command::Break),
+            ["w", "i"] => Ok(Command::Watch(Watch::Index)),
+            ["w", reg] if reg.len() == 2 && reg.starts_with('v') => usize::from_str_radix(&reg[1..], 16)
+                .map(|x| Command::Watch(Watch::Register(x)))
+                .map_err(|_| format!("invalid register {}", reg)),
+            ["w", addr] => parse_num(addr).map(|addr| Command::Watch(Watch::Memory(addr))),
+            ["v"] => Ok(Command::View),
+            ["h"] => Ok(Command::Help),
+            _ => Err(format!("unknown command {}, h for help", line.trim())),
+        }
+    }
+}
+
+// numbers may be given in decimal or in hex with a 0x prefix
+fn parse_num(s: &str) -> Result<usize, String> {
+    let num = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    num.map_err(|_| format!("invalid number {}", s))
+}
+
+#[derive(Default)]
+pub struct Debugger {
+    paused: bool,
+    // set when the execution stops, so resuming (or stepping) from a
+    // breakpoint executes its instruction instead of stopping again
+    resuming: bool,
+    breakpoints: BTreeSet<usize>,
+    watches: Vec<Watch>,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Debugger::default()
+    }
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+    pub fn pause(&mut self) {
+        self.paused = true;
+        self.resuming = true;
+    }
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+    // adds a breakpoint, or removes it if there was one already.
+    // Returns if there's a breakpoint now
+    pub fn toggle_breakpoint(&mut self, addr: usize) -> bool {
+        if self.breakpoints.remove(&addr) {
+            false
+        } else {
+            self.breakpoints.insert(addr);
+            true
+        }
+    }
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.iter().copied()
+    }
+    // adds a watchpoint, or removes it if there was one already. Returns
+    // if there's a watchpoint now, a bad address or register can't be watched
+    pub fn toggle_watch(&mut self, watch: Watch) -> bool {
+        match watch {
+            Watch::Memory(addr) if addr >= MEMORY_SIZE => return false,
+            Watch::Register(x) if x > 0xF => return false,
+            _ => (),
+        }
+        if let Some(i) = self.watches.iter().position(|w| *w == watch) {
+            self.watches.remove(i);
+            false
+        } else {
+            self.watches.push(watch);
+            true
+        }
+    }
+    pub fn watches(&self) -> &[Watch] {
+        &self.watches
+    }
+
+    // to be called instead of Chip8::run_frame, it does nothing while
+    // paused. When a breakpoint or watchpoint is hit the execution pauses
+    // and the reason is returned
+    pub fn run_frame(&mut self, chip8: &mut Chip8, cycles: usize) -> Result<Option<Stop>, Chip8Error> {
+        if self.paused {
+            return Ok(None);
+        }
+        self.run(chip8, cycles)
+    }
+    // executes a single instruction, even while paused
+    pub fn step(&mut self, chip8: &mut Chip8) -> Result<Option<Stop>, Chip8Error> {
+        self.resuming = true;
+        self.run(chip8, 1)
+    }
+    // executes a whole frame while paused, stopping early on a breakpoint
+    // or watchpoint. The frontend still has to tick the timers
+    pub fn step_frame(&mut self, chip8: &mut Chip8, cycles: usize) -> Result<Option<Stop>, Chip8Error> {
+        self.resuming = true;
+        self.run(chip8, cycles)
+    }
+    fn run(&mut self, chip8: &mut Chip8, cycles: usize) -> Result<Option<Stop>, Chip8Error> {
+        for _ in 0..cycles {
+            if !self.resuming && self.breakpoints.contains(&chip8.pc()) {
+                self.pause();
+                return Ok(Some(Stop::Breakpoint(chip8.pc())));
+            }
+            self.resuming = false;
+            let before: Vec<usize> = self.watches.iter().map(|w| w.read(chip8)).collect();
+            if let Err(e) = chip8.step() {
+                self.pause();
+                return Err(e);
+            }
+            for (watch, old) in self.watches.iter().zip(before) {
+                let new = watch.read(chip8);
+                if new != old {
+                    let stop = Stop::Watchpoint { watch: *watch, old, new };
+                    self.pause();
+                    return Ok(Some(stop));
+                }
+            }
+            if chip8.ends_frame() {
+                break;
+            }
+        }
+        Ok(None)
+    }
+
+    // the registers, the stack, the timers and the instructions around
+    // the pc, the current one marked by > and the breakpoints by *
+    pub fn view(&self, chip8: &Chip8) -> String {
+        let mut out = String::new();
+        let regs: Vec<String> = chip8
+            .registers()
+            .iter()
+            .enumerate()
+            .map(|(i, v)| format!("v{:X}: {:02X}", i, v))
+            .collect();
+        out += &format!("{}\n", regs[..8].join(" "));
+        out += &format!("{}\n", regs[8..].join(" "));
+        out += &format!(
+            "pc: {:#05X} i: {:#05X} dt: {} st: {}\n",
+            chip8.pc(),
+            chip8.index(),
+            chip8.delay_timer(),
+            chip8.sound_timer()
+        );
+        let stack: Vec<String> = chip8.stack()[..chip8.sp()]
+            .iter()
+            .map(|addr| format!("{:#05X}", addr))
+            .collect();
+        out += &format!("sp: {} stack: [{}]\n", chip8.sp(), stack.join(", "));
+
+        let memory = chip8.memory();
+        let pc = chip8.pc();
+        let start = pc.saturating_sub(5 * 2) & !1;
+        for addr in (start..pc + 6 * 2).step_by(2) {
+            if addr + 1 >= memory.len() {
+                break;
+            }
+            let opcode = (memory[addr] << 8) | memory[addr + 1];
+            out += &format!(
+                "{}{} {:#05X}: {:04X}  {}\n",
+                if addr == pc { '>' } else { ' ' },
+                if self.breakpoints.contains(&addr) { '*' } else { ' ' },
+                addr,
+                opcode,
+                disasm::mnemonic(opcode as u16, Syntax::Cowgod)
+            );
+        }
+        out
+    }
+}
@@ -0,0 +1,327 @@
+// turns instructions back into text, in the syntax of Octo or in the
+// classic one of Cowgod's reference, and whole roms into listings
+use crate::instruction::{decode, Instruction};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    Octo,
+    Cowgod,
+}
+
+impl Syntax {
+    pub fn from_name(name: &str) -> Option<Syntax> {
+        match name.to_lowercase().as_str() {
+            "octo" => Some(Syntax::Octo),
+            "cowgod" | "classic" => Some(Syntax::Cowgod),
+            _ => None,
+        }
+    }
+    fn comment(self) -> char {
+        match self {
+            Syntax::Octo => '#',
+            Syntax::Cowgod => ';',
+        }
+    }
+}
+
+// names given to addresses, used instead of the numbers
+pub type Labels = BTreeMap<usize, String>;
+
+// the text of a single instruction
+pub fn format(instruction: &Instruction, syntax: Syntax) -> String {
+    format_with_labels(instruction, syntax, &Labels::new())
+}
+
+// the text of an opcode, or the opcode as data if it's not an instruction
+pub fn mnemonic(opcode: u16, syntax: Syntax) -> String {
+    match (decode(opcode), syntax) {
+        (Ok(instruction), _) => format(&instruction, syntax),
+        (Err(_), Syntax::Octo) => format!("0x{:02X} 0x{:02X}", opcode >> 8, opcode & 0xFF),
+        (Err(_), Syntax::Cowgod) => format!("DW {:#06X}", opcode),
+    }
+}
+
+pub fn format_with_labels(instruction: &Instruction, syntax: Syntax, labels: &Labels) -> String {
+    let addr = |addr: &usize| match labels.get(addr) {
+        Some(label) => label.clone(),
+        None => format!("{:#05X}", addr),
+    };
+    match syntax {
+        Syntax::Octo => octo(instruction, addr),
+        Syntax::Cowgod => cowgod(instruction, addr),
+    }
+}
+
+fn octo(instruction: &Instruction, addr: impl Fn(&usize) -> String) -> String {
+    use Instruction::*;
+    match instruction {
+        ScrollDown(n) => format!("scroll-down {}", n),
+        ScrollUp(n) => format!("scroll-up {}", n),
+        Clear => "clear".to_string(),
+        Return => "return".to_string(),
+        ScrollRight => "scroll-right".to_string(),
+        ScrollLeft => "scroll-left".to_string(),
+        Exit => "exit".to_string(),
+        Lores => "lores".to_string(),
+        Hires => "hires".to_string(),
+        Jump(nnn) => format!("jump {}", addr(nnn)),
+        Call(nnn) => format!(":call {}", addr(nnn)),
+        // the skips are written as the condition for executing the next instruction
+        SkipEqConst { x, nn } => format!("if v{:x} != {:#04X} then", x, nn),
+        SkipNeConst { x, nn } => format!("if v{:x} == {:#04X} then", x, nn),
+        SkipEq { x, y } => format!("if v{:x} != v{:x} then", x, y),
+        SaveRange { x, y } => format!("save v{:x} - v{:x}", x, y),
+        LoadRange { x, y } => format!("load v{:x} - v{:x}", x, y),
+        SetConst { x, nn } => format!("v{:x} := {:#04X}", x, nn),
+        AddConst { x, nn } => format!("v{:x} += {:#04X}", x, nn),
+        Set { x, y } => format!("v{:x} := v{:x}", x, y),
+        Or { x, y } => format!("v{:x} |= v{:x}", x, y),
+        And { x, y } => format!("v{:x} &= v{:x}", x, y),
+        Xor { x, y } => format!("v{:x} ^= v{:x}", x, y),
+        Add { x, y } => format!("v{:x} += v{:x}", x, y),
+        Sub { x, y } => format!("v{:x} -= v{:x}", x, y),
+        ShiftRight { x, y } => format!("v{:x} >>= v{:x}", x, y),
+        SubN { x, y } => format!("v{:x} =- v{:x}", x, y),
+        ShiftLeft { x, y } => format!("v{:x} <<= v{:x}", x, y),
+        SkipNe { x, y } => format!("if v{:x} == v{:x} then", x, y),
+        SetI(nnn) => format!("i := {}", addr(nnn)),
+        JumpV0(nnn) => format!("jump0 {}", addr(nnn)),
+        Random { x, nn } => format!("v{:x} := random {:#04X}", x, nn),
+        Draw { x, y, n } => format!("sprite v{:x} v{:x} {}", x, y, n),
+        SkipKey(x) => format!("if v{:x} -key then", x),
+        SkipNotKey(x) => format!("if v{:x} key then", x),
+        LongI => "i := long".to_string(),
+        Planes(n) => format!("plane {}", n),
+        Audio => "audio".to_string(),
+        GetDelay(x) => format!("v{:x} := delay", x),
+        WaitKey(x) => format!("v{:x} := key", x),
+        SetDelay(x) => format!("delay := v{:x}", x),
+        SetSound(x) => format!("buzzer := v{:x}", x),
+        AddI(x) => format!("i += v{:x}", x),
+        Font(x) => format!("i := hex v{:x}", x),
+        BigFont(x) => format!("i := bighex v{:x}", x),
+        Bcd(x) => format!("bcd v{:x}", x),
+        Pitch(x) => format!("pitch := v{:x}", x),
+        Store(x) => format!("save v{:x}", x),
+        Load(x) => format!("load v{:x}", x),
+        StoreFlags(x) => format!("saveflags v{:x}", x),
+        LoadFlags(x) => format!("loadflags v{:x}", x),
+    }
+}
+
+fn cowgod(instruction: &Instruction, addr: impl Fn(&usize) -> String) -> String {
+    use Instruction::*;
+    match instruction {
+        ScrollDown(n) => format!("SCD {}", n),
+        ScrollUp(n) => format!("SCU {}", n),
+        Clear => "CLS".to_string(),
+        Return => "RET".to_string(),
+        ScrollRight => "SCR".to_string(),
+        ScrollLeft => "SCL".to_string(),
+        Exit => "EXIT".to_string(),
+        Lores => "LOW".to_string(),
+        Hires => "HIGH".to_string(),
+        Jump(nnn) => format!("JP {}", addr(nnn)),
+        Call(nnn) => format!("CALL {}", addr(nnn)),
+        SkipEqConst { x, nn } => format!("SE V{:X}, {:#04X}", x, nn),
+        SkipNeConst { x, nn } => format!("SNE V{:X}, {:#04X}", x, nn),
+        SkipEq { x, y } => format!("SE V{:X}, V{:X}", x, y),
+        SaveRange { x, y } => format!("SAVE V{:X}, V{:X}", x, y),
+        LoadRange { x, y } => format!("LOAD V{:X}, V{:X}", x, y),
+        SetConst { x, nn } => format!("LD V{:X}, {:#04X}", x, nn),
+        AddConst { x, nn } => format!("ADD V{:X}, {:#04X}", x, nn),
+        Set { x, y } => format!("LD V{:X}, V{:X}", x, y),
+        Or { x, y } => format!("OR V{:X}, V{:X}", x, y),
+        And { x, y } => format!("AND V{:X}, V{:X}", x, y),
+        Xor { x, y } => format!("XOR V{:X}, V{:X}", x, y),
+        Add { x, y } => format!("ADD V{:X}, V{:X}", x, y),
+        Sub { x, y } => format!("SUB V{:X}, V{:X}", x, y),
+        ShiftRight { x, y } => format!("SHR V{:X}, V{:X}", x, y),
+        SubN { x, y } => format!("SUBN V{:X}, V{:X}", x, y),
+        ShiftLeft { x, y } => format!("SHL V{:X}, V{:X}", x, y),
+        SkipNe { x, y } => format!("SNE V{:X}, V{:X}", x, y),
+        SetI(nnn) => format!("LD I, {}", addr(nnn)),
+        JumpV0(nnn) => format!("JP V0, {}", addr(nnn)),
+        Random { x, nn } => format!("RND V{:X}, {:#04X}", x, nn),
+        Draw { x, y, n } => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        SkipKey(x) => format!("SKP V{:X}", x),
+        SkipNotKey(x) => format!("SKNP V{:X}", x),
+        LongI => "LD I, LONG".to_string(),
+        Planes(n) => format!("PLANE {}", n),
+        Audio => "AUDIO".to_string(),
+        GetDelay(x) => format!("LD V{:X}, DT", x),
+        WaitKey(x) => format!("LD V{:X}, K", x),
+        SetDelay(x) => format!("LD DT, V{:X}", x),
+        SetSound(x) => format!("LD ST, V{:X}", x),
+        AddI(x) => format!("ADD I, V{:X}", x),
+        Font(x) => format!("LD F, V{:X}", x),
+        BigFont(x) => format!("LD HF, V{:X}", x),
+        Bcd(x) => format!("LD B, V{:X}", x),
+        Pitch(x) => format!("PITCH V{:X}", x),
+        Store(x) => format!("LD [I], V{:X}", x),
+        Load(x) => format!("LD V{:X}, [I]", x),
+        StoreFlags(x) => format!("LD R, V{:X}", x),
+        LoadFlags(x) => format!("LD V{:X}, R", x),
+    }
+}
+
+// what the rom looks like to the disassembler: the addresses where
+// an instruction starts and the names of the addresses referenced
+struct Analysis {
+    code: BTreeSet<usize>,
+    labels: Labels,
+}
+
+fn word(rom: &[u8], origin: usize, addr: usize) -> Option<u16> {
+    let pos = addr.checked_sub(origin)?;
+    match (rom.get(pos), rom.get(pos + 1)) {
+        (Some(high), Some(low)) => Some(((*high as u16) << 8) | *low as u16),
+        _ => None,
+    }
+}
+
+// follows the flow of the program from its entry point, whatever
+// isn't reached that way is considered data. The targets of jumps and
+// calls get a label, as do the addresses loaded into I (the sprites)
+fn analyse(rom: &[u8], origin: usize) -> Analysis {
+    let end = origin + rom.len();
+    let mut code = BTreeSet::new();
+    let mut labels = Labels::new();
+    // a label for an address in the rom, a subroutine name wins over
+    // a jump target name which wins over a data name
+    let mut label = |addr: usize, prefix: &str| {
+        if addr < origin || addr >= end {
+            return;
+        }
+        let rank = |name: &str| ["data", "label", "sub"].iter().position(|p| name.starts_with(p));
+        let name = format!("{}_{:03X}", prefix, addr);
+        match labels.get(&addr) {
+            Some(old) if rank(old) >= rank(&name) => (),
+            _ => {
+                labels.insert(addr, name);
+            }
+        }
+    };
+
+    let mut pending = vec![origin];
+    while let Some(addr) = pending.pop() {
+        if code.contains(&addr) {
+            continue;
+        }
+        let instruction = match word(rom, origin, addr).and_then(|w| decode(w).ok()) {
+            Some(instruction) => instruction,
+            None => continue,
+        };
+        if instruction == Instruction::LongI {
+            match word(rom, origin, addr + 2) {
+                Some(target) => label(target as usize, "data"),
+                None => continue,
+            }
+        }
+        code.insert(addr);
+        let next = addr + instruction.size();
+        match instruction {
+            Instruction::Jump(target) => {
+                label(target, "label");
+                pending.push(target);
+            }
+            Instruction::Call(target) => {
+                label(target, "sub");
+                pending.push(target);
+                pending.push(next);
+            }
+            // the table of jumps usually starts at the address
+            Instruction::JumpV0(target) => {
+                label(target, "label");
+                pending.push(target);
+            }
+            Instruction::Return | Instruction::Exit => (),
+            Instruction::SetI(target) => {
+                label(target, "data");
+                pending.push(next);
+            }
+            _ if instruction.is_skip() => {
+                pending.push(next);
+                let skipped = word(rom, origin, next).and_then(|w| decode(w).ok()).map_or(2, |i| i.size());
+                pending.push(next + skipped);
+            }
+            _ => pending.push(next),
+        }
+    }
+    Analysis { code, labels }
+}
+
+// the whole rom, loaded at origin, as a program in the given syntax.
+// Each line has the address and the bytes as a comment
+pub fn listing(rom: &[u8], origin: usize, syntax: Syntax) -> String {
+    let Analysis { code, labels } = analyse(rom, origin);
+    let end = origin + rom.len();
+    let comment = syntax.comment();
+    let mut out = String::new();
+    if origin != 0x200 {
+        match syntax {
+            Syntax::Octo => out += &format!(":org {:#05X}\n", origin),
+            Syntax::Cowgod => out += &format!("ORG {:#05X}\n", origin),
+        }
+    }
+
+    // Octo programs start at main
+    if syntax == Syntax::Octo {
+        out += ": main\n";
+    }
+    let mut addr = origin;
+    while addr < end {
+        if let Some(label) = labels.get(&addr) {
+            match syntax {
+                Syntax::Octo => out += &format!(": {}\n", label),
+                Syntax::Cowgod => out += &format!("{}:\n", label),
+            }
+        }
+        let instruction = word(rom, origin, addr).and_then(|w| decode(w).ok());
+        // an instruction with a label in its middle is printed as data,
+        // so the label still has somewhere to go
+        let fits = |size: usize| addr + size <= end && labels.range(addr + 1..addr + size).next().is_none();
+        match instruction {
+            Some(instruction) if code.contains(&addr) && fits(instruction.size()) => {
+                let size = instruction.size();
+                let mut text = format_with_labels(&instruction, syntax, &labels);
+                if instruction == Instruction::LongI {
+                    let target = word(rom, origin, addr + 2).expect("checked by analyse") as usize;
+                    text += &match labels.get(&target) {
+                        Some(label) => format!(" {}", label),
+                        None => format!(" {:#06X}", target),
+                    };
+                }
+                let bytes: Vec<String> = rom[addr - origin..addr - origin + size]
+                    .iter()
+                    .map(|b| format!("{:02X}", b))
+                    .collect();
+                out += &format!("    {:<27} {} {:#05X}  {}\n", text, comment, addr, bytes.concat());
+                addr += size;
+            }
+            _ => {
+                // data goes on until the next instruction or label, 8 bytes per line
+                let mut len = 1;
+                while len < 8
+                    && addr + len < end
+                    && !code.contains(&(addr + len))
+                    && !labels.contains_key(&(addr + len))
+                {
+                    len += 1;
+                }
+                let bytes: Vec<String> = rom[addr - origin..addr - origin + len]
+                    .iter()
+                    .map(|b| format!("{:#04X}", b))
+                    .collect();
+                let text = match syntax {
+                    Syntax::Octo => bytes.join(" "),
+                    Syntax::Cowgod => format!("DB {}", bytes.join(", ")),
+                };
+                out += &format!("    {:<27} {} {:#05X}\n", text, comment, addr);
+                addr += len;
+            }
+        }
+    }
+    out
+}
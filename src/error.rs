@@ -0,0 +1,169 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+// errors that stop the execution of a rom, all of them carry the
+// address and the opcode of the faulty instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8Error {
+    // the opcode isn't part of the instruction set
+    UnknownOpcode { pc: usize, opcode: usize },
+    // a 2NNN with all the 16 stack levels in use
+    StackOverflow { pc: usize, opcode: usize },
+    // a 00EE without any function call to return from
+    StackUnderflow { pc: usize, opcode: usize },
+    // the instruction tried to access an address past the end of memory
+    MemoryOutOfBounds { pc: usize, opcode: usize, addr: usize },
+    // EX9E/EXA1 with a VX that isn't one of the 16 keys
+    InvalidKey { pc: usize, opcode: usize, key: usize },
+}
+
+impl Chip8Error {
+    // address of the instruction that caused the error
+    pub fn pc(&self) -> usize {
+        match *self {
+            Chip8Error::UnknownOpcode { pc, .. }
+            | Chip8Error::StackOverflow { pc, .. }
+            | Chip8Error::StackUnderflow { pc, .. }
+            | Chip8Error::MemoryOutOfBounds { pc, .. }
+            | Chip8Error::InvalidKey { pc, .. } => pc,
+        }
+    }
+    // the instruction that caused the error
+    pub fn opcode(&self) -> usize {
+        match *self {
+            Chip8Error::UnknownOpcode { opcode, .. }
+            | Chip8Error::StackOverflow { opcode, .. }
+            | Chip8Error::StackUnderflow { opcode, .. }
+            | Chip8Error::MemoryOutOfBounds { opcode, .. }
+            | Chip8Error::InvalidKey { opcode, .. } => opcode,
+        }
+    }
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Chip8Error::UnknownOpcode { .. } => write!(f, "unknown opcode")?,
+            Chip8Error::StackOverflow { .. } => write!(f, "stack overflow")?,
+            Chip8Error::StackUnderflow { .. } => write!(f, "stack underflow")?,
+            Chip8Error::MemoryOutOfBounds { addr, .. } => {
+                write!(f, "memory access out of bounds at {:#05X}", addr)?
+            }
+            Chip8Error::InvalidKey { key, .. } => write!(f, "invalid key {:#X}", key)?,
+        }
+        write!(f, " (opcode {:04X} at {:#05X})", self.opcode(), self.pc())
+    }
+}
+
+impl Error for Chip8Error {}
+
+// an opcode that isn't part of the instruction set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeError {
+    pub opcode: u16,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown opcode {:04X}", self.opcode)
+    }
+}
+
+impl Error for DecodeError {}
+
+// errors while loading a rom into memory
+#[derive(Debug)]
+pub enum RomError {
+    // the rom couldn't be read from its source
+    Io(io::Error),
+    // the rom doesn't fit in the memory after the start address
+    TooLarge { size: usize, max: usize },
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RomError::Io(e) => write!(f, "unable to read rom: {}", e),
+            RomError::TooLarge { size, max } => write!(
+                f,
+                "rom is too large: {} bytes, the maximum is {} bytes",
+                size, max
+            ),
+        }
+    }
+}
+
+impl Error for RomError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RomError::Io(e) => Some(e),
+            RomError::TooLarge { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for RomError {
+    fn from(e: io::Error) -> Self {
+        RomError::Io(e)
+    }
+}
+
+// errors while restoring a save state
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateError {
+    // the data isn't a save state, or it's damaged
+    Corrupted,
+    // the state was saved by an incompatible version of the emulator
+    Version { found: u16, expected: u16 },
+    // the state was saved while running a different rom
+    RomMismatch,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::Corrupted => write!(f, "not a valid save state"),
+            StateError::Version { found, expected } => write!(
+                f,
+                "save state version {} is not supported, expected version {}",
+                found, expected
+            ),
+            StateError::RomMismatch => write!(f, "save state belongs to a different rom"),
+        }
+    }
+}
+
+impl Error for StateError {}
+
+// an error in an assembly source, with where it happened
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+// an error in a configuration file, with where it happened
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.message)
+    }
+}
+
+impl Error for ConfigError {}
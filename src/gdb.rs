@@ -0,0 +1,460 @@
+// a stub for the GDB remote serial protocol, so gdb (or anything else
+// speaking it) can drive the interpreter over a TCP connection:
+//
+//     (gdb) target remote localhost:1234
+//
+// The registers are V0 to VF (8 bits), I and PC (16 bits, little endian
+// like every multi-byte value of the protocol), SP, DT and ST (8 bits),
+// described to gdb by target.xml. Memory can be read and written, and
+// software breakpoints (Z0), single-step and continue go through the
+// Debugger. While running, the timers tick at 60Hz and the execution can
+// be interrupted with ^C
+use crate::cpu::{Chip8, MEMORY_SIZE};
+use crate::debugger::{Debugger, Stop};
+use crate::error::Chip8Error;
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// the number of registers in the g packet, V0-VF, I, PC, SP, DT and ST
+const REGISTERS: usize = 21;
+// signals reported in the stop replies
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+pub struct GdbStub {
+    debugger: Debugger,
+    // instructions per frame while continuing
+    ipf: usize,
+}
+
+// what a packet asks the stub to do after answering
+enum Next {
+    Serve,
+    Close,
+}
+
+impl GdbStub {
+    pub fn new(ipf: usize) -> Self {
+        let mut debugger = Debugger::new();
+        // the program is stopped until gdb says otherwise
+        debugger.pause();
+        GdbStub { debugger, ipf }
+    }
+
+    // answers the packets of one connection, until gdb detaches, kills
+    // the program or goes away, or until the rom exits
+    pub fn serve(&mut self, chip8: &mut Chip8, stream: TcpStream) -> io::Result<()> {
+        // the packets are tiny and each waits for the previous one
+        stream.set_nodelay(true)?;
+        let mut conn = Connection::new(stream);
+        while let Some(packet) = conn.packet()? {
+            if let Next::Close = self.handle(chip8, &mut conn, &packet)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, chip8: &mut Chip8, conn: &mut Connection, packet: &str) -> io::Result<Next> {
+        let (command, args) = packet.split_at(packet.len().min(1));
+        let reply = match command {
+            // ^C while stopped, there's nothing to interrupt
+            "\x03" => stop_reply(SIGINT),
+            "?" => stop_reply(SIGTRAP),
+            "q" => query(args),
+            "Q" if args == "StartNoAckMode" => {
+                conn.send("OK")?;
+                conn.ack = false;
+                return Ok(Next::Serve);
+            }
+            "H" => "OK".to_string(),
+            "g" => (0..REGISTERS).map(|n| read_register(chip8, n)).collect(),
+            "G" => {
+                let mut rest = args;
+                for n in 0..REGISTERS {
+                    let width = register_width(n) * 2;
+                    if rest.len() < width || !write_register(chip8, n, &rest[..width]) {
+                        break;
+                    }
+                    rest = &rest[width..];
+                }
+                if rest.is_empty() {
+                    "OK".to_string()
+                } else {
+                    "E01".to_string()
+                }
+            }
+            "p" => match usize::from_str_radix(args, 16) {
+                Ok(n) if n < REGISTERS => read_register(chip8, n),
+                _ => "E01".to_string(),
+            },
+            "P" => {
+                let written = args.split_once('=').and_then(|(n, value)| {
+                    let n = usize::from_str_radix(n, 16).ok().filter(|n| *n < REGISTERS)?;
+                    Some(value.len() == register_width(n) * 2 && write_register(chip8, n, value))
+                });
+                if written == Some(true) {
+                    "OK".to_string()
+                } else {
+                    "E01".to_string()
+                }
+            }
+            // the lengths come from the client, any of them can overflow
+            "m" => match parse_range(args).and_then(|(addr, len)| Some((addr, addr.checked_add(len)?))) {
+                Some((addr, end)) if addr < MEMORY_SIZE => {
+                    let end = end.min(MEMORY_SIZE);
+                    chip8.memory()[addr..end]
+                        .iter()
+                        .map(|b| format!("{:02x}", b))
+                        .collect()
+                }
+                _ => "E01".to_string(),
+            },
+            "M" => {
+                let written = args.split_once(':').and_then(|(range, data)| {
+                    let (addr, len) = parse_range(range)?;
+                    let bytes = from_hex(data)?;
+                    if bytes.len() != len || addr.checked_add(len)? > MEMORY_SIZE {
+                        return None;
+                    }
+                    for (i, byte) in bytes.into_iter().enumerate() {
+                        chip8.set_memory(addr + i, byte);
+                    }
+                    Some(())
+                });
+                match written {
+                    Some(()) => "OK".to_string(),
+                    None => "E01".to_string(),
+                }
+            }
+            "Z" | "z" => match args.strip_prefix("0,").and_then(parse_range) {
+                Some((addr, _)) => {
+                    let set = self.debugger.breakpoints().any(|b| b == addr);
+                    if set != (command == "Z") {
+                        self.debugger.toggle_breakpoint(addr);
+                    }
+                    "OK".to_string()
+                }
+                // only software breakpoints are supported
+                None => String::new(),
+            },
+            "s" | "c" => {
+                if let Ok(addr) = usize::from_str_radix(args, 16) {
+                    chip8.set_pc(addr);
+                }
+                let reply = if command == "s" {
+                    self.step(chip8)
+                } else {
+                    self.resume(chip8, conn)?
+                };
+                conn.send(&reply)?;
+                return Ok(if reply.starts_with('W') {
+                    Next::Close
+                } else {
+                    Next::Serve
+                });
+            }
+            "D" => {
+                conn.send("OK")?;
+                return Ok(Next::Close);
+            }
+            "k" => return Ok(Next::Close),
+            // an empty reply tells gdb the packet isn't supported
+            _ => String::new(),
+        };
+        conn.send(&reply)?;
+        Ok(Next::Serve)
+    }
+
+    fn step(&mut self, chip8: &mut Chip8) -> String {
+        if chip8.is_halted() {
+            return "W00".to_string();
+        }
+        match self.debugger.step(chip8) {
+            Ok(Some(stop)) => stopped(stop),
+            Ok(None) => stop_reply(SIGTRAP),
+            Err(e) => failed(e),
+        }
+    }
+
+    // runs frame after frame at 60Hz until something stops the execution
+    fn resume(&mut self, chip8: &mut Chip8, conn: &mut Connection) -> io::Result<String> {
+        let frame = Duration::from_secs(1) / 60;
+        let mut next = Instant::now();
+        self.debugger.resume();
+        loop {
+            if chip8.is_halted() {
+                return Ok("W00".to_string());
+            }
+            match self.debugger.run_frame(chip8, self.ipf) {
+                Ok(Some(stop)) => return Ok(stopped(stop)),
+                Ok(None) => (),
+                Err(e) => return Ok(failed(e)),
+            }
+            chip8.tick_timers();
+            if conn.interrupted()? {
+                self.debugger.pause();
+                return Ok(stop_reply(SIGINT));
+            }
+            next += frame;
+            if let Some(wait) = next.checked_duration_since(Instant::now()) {
+                thread::sleep(wait);
+            }
+        }
+    }
+}
+
+fn query(args: &str) -> String {
+    if args.starts_with("Supported") {
+        return "PacketSize=1000;qXfer:features:read+;swbreak+;QStartNoAckMode+".to_string();
+    }
+    if let Some(range) = args.strip_prefix("Xfer:features:read:target.xml:") {
+        return match parse_range(range) {
+            Some((offset, len)) => {
+                let xml = target_xml();
+                let start = offset.min(xml.len());
+                let end = (start + len).min(xml.len());
+                let more = if end < xml.len() { 'm' } else { 'l' };
+                format!("{}{}", more, &xml[start..end])
+            }
+            None => "E01".to_string(),
+        };
+    }
+    match args {
+        "Attached" => "1".to_string(),
+        "C" => "QC1".to_string(),
+        "fThreadInfo" => "m1".to_string(),
+        "sThreadInfo" => "l".to_string(),
+        _ => String::new(),
+    }
+}
+
+fn target_xml() -> String {
+    let mut regs = String::new();
+    for x in 0..16 {
+        regs += &format!("<reg name=\"v{:x}\" bitsize=\"8\" type=\"uint8\"/>", x);
+    }
+    regs += "<reg name=\"i\" bitsize=\"16\" type=\"data_ptr\"/>";
+    regs += "<reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\"/>";
+    for name in &["sp", "dt", "st"] {
+        regs += &format!("<reg name=\"{}\" bitsize=\"8\" type=\"uint8\"/>", name);
+    }
+    format!(
+        "<?xml version=\"1.0\"?><!DOCTYPE target SYSTEM \"gdb-target.dtd\">\
+         <target version=\"1.0\"><feature name=\"org.chip8.core\">{}</feature></target>",
+        regs
+    )
+}
+
+// in bytes
+fn register_width(n: usize) -> usize {
+    match n {
+        16 | 17 => 2,
+        _ => 1,
+    }
+}
+
+fn read_register(chip8: &Chip8, n: usize) -> String {
+    let value = match n {
+        0..=15 => chip8.registers()[n],
+        16 => chip8.index(),
+        17 => chip8.pc(),
+        18 => chip8.sp(),
+        19 => chip8.delay_timer(),
+        _ => chip8.sound_timer(),
+    };
+    (0..register_width(n))
+        .map(|i| format!("{:02x}", (value >> (i * 8)) & 0xFF))
+        .collect()
+}
+
+// returns false if the value isn't valid for the register
+fn write_register(chip8: &mut Chip8, n: usize, hex: &str) -> bool {
+    let value = match from_hex(hex) {
+        Some(bytes) => bytes
+            .iter()
+            .rev()
+            .fold(0, |value, byte| (value << 8) | *byte as usize),
+        None => return false,
+    };
+    match n {
+        0..=15 => chip8.set_register(n, value),
+        16 => chip8.set_index(value),
+        17 => chip8.set_pc(value),
+        18 if value <= chip8.stack().len() => chip8.set_sp(value),
+        18 => return false,
+        19 => chip8.set_delay_timer(value),
+        _ => chip8.set_sound_timer(value),
+    }
+    true
+}
+
+fn stop_reply(signal: u8) -> String {
+    format!("S{:02x}", signal)
+}
+
+fn stopped(stop: Stop) -> String {
+    match stop {
+        Stop::Breakpoint(_) => format!("T{:02x}swbreak:;", SIGTRAP),
+        Stop::Watchpoint { .. } => stop_reply(SIGTRAP),
+    }
+}
+
+// the error is shown by gdb as the output of the program
+fn failed(e: Chip8Error) -> String {
+    let signal = match e {
+        Chip8Error::UnknownOpcode { .. } | Chip8Error::InvalidKey { .. } => SIGILL,
+        _ => SIGSEGV,
+    };
+    let message: String = format!("{}\n", e).bytes().map(|b| format!("{:02x}", b)).collect();
+    format!("O{}\0{}", message, stop_reply(signal))
+}
+
+// ADDR,LENGTH in hex
+fn parse_range(s: &str) -> Option<(usize, usize)> {
+    let (addr, len) = s.split_once(',')?;
+    Some((
+        usize::from_str_radix(addr, 16).ok()?,
+        usize::from_str_radix(len, 16).ok()?,
+    ))
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    // an odd length leaves the last digit alone, get fails on it
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+// the framing of the packets: $data#checksum, acknowledged with + (or
+// - to get it again) until gdb asks for the no ack mode
+struct Connection {
+    stream: TcpStream,
+    // bytes received while checking for an interruption
+    pending: VecDeque<u8>,
+    ack: bool,
+    // set when gdb went away while the program was running
+    closed: bool,
+    // kept to send it again when gdb didn't get it right
+    last: Vec<u8>,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> Self {
+        Connection {
+            stream,
+            pending: VecDeque::new(),
+            ack: true,
+            closed: false,
+            last: Vec::new(),
+        }
+    }
+
+    // None when gdb closed the connection
+    fn byte(&mut self) -> io::Result<Option<u8>> {
+        if let Some(byte) = self.pending.pop_front() {
+            return Ok(Some(byte));
+        }
+        if self.closed {
+            return Ok(None);
+        }
+        let mut buf = [0];
+        match self.stream.read(&mut buf)? {
+            0 => Ok(None),
+            _ => Ok(Some(buf[0])),
+        }
+    }
+
+    // the next packet, an interruption being returned as "\x03"
+    fn packet(&mut self) -> io::Result<Option<String>> {
+        loop {
+            match self.byte()? {
+                None => return Ok(None),
+                Some(b'$') => (),
+                Some(0x03) => return Ok(Some("\x03".to_string())),
+                Some(b'-') => {
+                    let last = self.last.clone();
+                    self.stream.write_all(&last)?;
+                    continue;
+                }
+                // acks and noise
+                Some(_) => continue,
+            }
+            let mut data = Vec::new();
+            loop {
+                match self.byte()? {
+                    None => return Ok(None),
+                    Some(b'#') => break,
+                    Some(byte) => data.push(byte),
+                }
+            }
+            let mut checksum = [0; 2];
+            for digit in checksum.iter_mut() {
+                match self.byte()? {
+                    None => return Ok(None),
+                    Some(byte) => *digit = byte,
+                }
+            }
+            let expected = std::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            let sum = data.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+            if self.ack {
+                if expected != Some(sum) {
+                    self.stream.write_all(b"-")?;
+                    continue;
+                }
+                self.stream.write_all(b"+")?;
+            }
+            return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+        }
+    }
+
+    // a reply can hold several packets separated by \0, for the
+    // output sent before a stop reply
+    fn send(&mut self, reply: &str) -> io::Result<()> {
+        let mut out = Vec::new();
+        for data in reply.split('\0') {
+            let sum = data.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+            out.extend(format!("${}#{:02x}", data, sum).bytes());
+        }
+        if !self.closed {
+            self.stream.write_all(&out)?;
+        }
+        self.last = out;
+        Ok(())
+    }
+
+    // checks, without blocking, if gdb sent a ^C (or closed the connection)
+    fn interrupted(&mut self) -> io::Result<bool> {
+        self.stream.set_nonblocking(true)?;
+        let mut buf = [0; 64];
+        let result = loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => {
+                    self.closed = true;
+                    break Ok(());
+                }
+                Ok(n) => self.pending.extend(&buf[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break Ok(()),
+                Err(e) => break Err(e),
+            }
+        };
+        self.stream.set_nonblocking(false)?;
+        result?;
+        if self.closed {
+            return Ok(true);
+        }
+        if let Some(i) = self.pending.iter().position(|b| *b == 0x03) {
+            self.pending.remove(i);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+}
@@ -0,0 +1,197 @@
+use crate::error::DecodeError;
+
+// the instruction set, decoded from the opcodes into a typed form.
+// It covers CHIP-8, SUPER-CHIP 1.1 and XO-CHIP, x and y are register
+// numbers, n, nn and nnn the 4, 8 and 12 bits constants
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    // 00CN
+    ScrollDown(usize),
+    // 00DN
+    ScrollUp(usize),
+    // 00E0
+    Clear,
+    // 00EE
+    Return,
+    // 00FB
+    ScrollRight,
+    // 00FC
+    ScrollLeft,
+    // 00FD
+    Exit,
+    // 00FE
+    Lores,
+    // 00FF
+    Hires,
+    // 1NNN
+    Jump(usize),
+    // 2NNN
+    Call(usize),
+    // 3XNN
+    SkipEqConst { x: usize, nn: usize },
+    // 4XNN
+    SkipNeConst { x: usize, nn: usize },
+    // 5XY0
+    SkipEq { x: usize, y: usize },
+    // 5XY2
+    SaveRange { x: usize, y: usize },
+    // 5XY3
+    LoadRange { x: usize, y: usize },
+    // 6XNN
+    SetConst { x: usize, nn: usize },
+    // 7XNN
+    AddConst { x: usize, nn: usize },
+    // 8XY0
+    Set { x: usize, y: usize },
+    // 8XY1
+    Or { x: usize, y: usize },
+    // 8XY2
+    And { x: usize, y: usize },
+    // 8XY3
+    Xor { x: usize, y: usize },
+    // 8XY4
+    Add { x: usize, y: usize },
+    // 8XY5
+    Sub { x: usize, y: usize },
+    // 8XY6
+    ShiftRight { x: usize, y: usize },
+    // 8XY7
+    SubN { x: usize, y: usize },
+    // 8XYE
+    ShiftLeft { x: usize, y: usize },
+    // 9XY0
+    SkipNe { x: usize, y: usize },
+    // ANNN
+    SetI(usize),
+    // BNNN, BXNN with the jump quirk
+    JumpV0(usize),
+    // CXNN
+    Random { x: usize, nn: usize },
+    // DXYN
+    Draw { x: usize, y: usize, n: usize },
+    // EX9E
+    SkipKey(usize),
+    // EXA1
+    SkipNotKey(usize),
+    // F000 NNNN, the address is the word following the opcode
+    LongI,
+    // FN01
+    Planes(usize),
+    // F002
+    Audio,
+    // FX07
+    GetDelay(usize),
+    // FX0A
+    WaitKey(usize),
+    // FX15
+    SetDelay(usize),
+    // FX18
+    SetSound(usize),
+    // FX1E
+    AddI(usize),
+    // FX29
+    Font(usize),
+    // FX30
+    BigFont(usize),
+    // FX33
+    Bcd(usize),
+    // FX3A
+    Pitch(usize),
+    // FX55
+    Store(usize),
+    // FX65
+    Load(usize),
+    // FX75
+    StoreFlags(usize),
+    // FX85
+    LoadFlags(usize),
+}
+
+impl Instruction {
+    // the size in bytes, including the address of F000 NNNN
+    pub fn size(&self) -> usize {
+        match self {
+            Instruction::LongI => 4,
+            _ => 2,
+        }
+    }
+    // true for the instructions skipping the next one
+    pub fn is_skip(&self) -> bool {
+        matches!(
+            self,
+            Instruction::SkipEqConst { .. }
+                | Instruction::SkipNeConst { .. }
+                | Instruction::SkipEq { .. }
+                | Instruction::SkipNe { .. }
+                | Instruction::SkipKey(_)
+                | Instruction::SkipNotKey(_)
+        )
+    }
+}
+
+// the instruction of an opcode, it has no side effects so anything
+// can use it to know what an opcode means
+pub fn decode(opcode: u16) -> Result<Instruction, DecodeError> {
+    use Instruction::*;
+    let error = DecodeError { opcode };
+    let opcode = opcode as usize;
+    let x = (opcode & 0x0F00) >> 8;
+    let y = (opcode & 0x00F0) >> 4;
+    let n = opcode & 0x000F;
+    let nn = opcode & 0x00FF;
+    let nnn = opcode & 0x0FFF;
+    let instruction = match (opcode >> 12, x, y, n) {
+        (0, 0, 0xC, _) => ScrollDown(n),
+        (0, 0, 0xD, _) => ScrollUp(n),
+        (0, 0, 0xE, 0) => Clear,
+        (0, 0, 0xE, 0xE) => Return,
+        (0, 0, 0xF, 0xB) => ScrollRight,
+        (0, 0, 0xF, 0xC) => ScrollLeft,
+        (0, 0, 0xF, 0xD) => Exit,
+        (0, 0, 0xF, 0xE) => Lores,
+        (0, 0, 0xF, 0xF) => Hires,
+        (1, _, _, _) => Jump(nnn),
+        (2, _, _, _) => Call(nnn),
+        (3, _, _, _) => SkipEqConst { x, nn },
+        (4, _, _, _) => SkipNeConst { x, nn },
+        (5, _, _, 0) => SkipEq { x, y },
+        (5, _, _, 2) => SaveRange { x, y },
+        (5, _, _, 3) => LoadRange { x, y },
+        (6, _, _, _) => SetConst { x, nn },
+        (7, _, _, _) => AddConst { x, nn },
+        (8, _, _, 0) => Set { x, y },
+        (8, _, _, 1) => Or { x, y },
+        (8, _, _, 2) => And { x, y },
+        (8, _, _, 3) => Xor { x, y },
+        (8, _, _, 4) => Add { x, y },
+        (8, _, _, 5) => Sub { x, y },
+        (8, _, _, 6) => ShiftRight { x, y },
+        (8, _, _, 7) => SubN { x, y },
+        (8, _, _, 0xE) => ShiftLeft { x, y },
+        (9, _, _, 0) => SkipNe { x, y },
+        (0xA, _, _, _) => SetI(nnn),
+        (0xB, _, _, _) => JumpV0(nnn),
+        (0xC, _, _, _) => Random { x, nn },
+        (0xD, _, _, _) => Draw { x, y, n },
+        (0xE, _, 9, 0xE) => SkipKey(x),
+        (0xE, _, 0xA, 1) => SkipNotKey(x),
+        (0xF, 0, 0, 0) => LongI,
+        (0xF, _, 0, 1) => Planes(x),
+        (0xF, 0, 0, 2) => Audio,
+        (0xF, _, 0, 7) => GetDelay(x),
+        (0xF, _, 0, 0xA) => WaitKey(x),
+        (0xF, _, 1, 5) => SetDelay(x),
+        (0xF, _, 1, 8) => SetSound(x),
+        (0xF, _, 1, 0xE) => AddI(x),
+        (0xF, _, 2, 9) => Font(x),
+        (0xF, _, 3, 0) => BigFont(x),
+        (0xF, _, 3, 3) => Bcd(x),
+        (0xF, _, 3, 0xA) => Pitch(x),
+        (0xF, _, 5, 5) => Store(x),
+        (0xF, _, 6, 5) => Load(x),
+        (0xF, _, 7, 5) => StoreFlags(x),
+        (0xF, _, 8, 5) => LoadFlags(x),
+        _ => return Err(error),
+    };
+    Ok(instruction)
+}
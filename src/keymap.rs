@@ -0,0 +1,123 @@
+// which keys of the host keyboard press each key of the CHIP-8 keypad.
+// The host keys are named like the key codes of the frontend (winit's
+// VirtualKeyCode: "Key1", "Q", "Numpad7", "Up"...), the default layout
+// being the usual one, the left side of a QWERTY keyboard:
+//
+//     1 2 3 C        1 2 3 4
+//     4 5 6 D        Q W E R
+//     7 8 9 E   ->   A S D F
+//     A 0 B F        Z X C V
+//
+// Gamepads are mapped the same way, the buttons being named like gilrs
+// names them ("South", "DPadUp", "Start"...) and the axes by their
+// name and a direction ("LeftStickX-" is the left stick pushed left)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    // the host keys of each CHIP-8 key
+    keys: [Vec<String>; 16],
+}
+
+// the CHIP-8 keys in the order of the keypad, row by row
+const KEYPAD: [usize; 16] = [0x1, 0x2, 0x3, 0xC, 0x4, 0x5, 0x6, 0xD, 0x7, 0x8, 0x9, 0xE, 0xA, 0x0, 0xB, 0xF];
+
+// the host keys for each position of the keypad
+const QWERTY: [&str; 16] = [
+    "Key1", "Key2", "Key3", "Key4", "Q", "W", "E", "R", "A", "S", "D", "F", "Z", "X", "C", "V",
+];
+const AZERTY: [&str; 16] = [
+    "Key1", "Key2", "Key3", "Key4", "A", "Z", "E", "R", "Q", "S", "D", "F", "W", "X", "C", "V",
+];
+const DVORAK: [&str; 16] = [
+    "Key1", "Key2", "Key3", "Key4", "Apostrophe", "Comma", "Period", "P", "A", "O", "E", "U", "Semicolon",
+    "Q", "J", "K",
+];
+// the digits on the same digits, A to F around them
+const NUMPAD: [&str; 16] = [
+    "Numpad7", "Numpad8", "Numpad9", "Subtract", "Numpad4", "Numpad5", "Numpad6", "Add", "Numpad1",
+    "Numpad2", "Numpad3", "NumpadEnter", "Divide", "Numpad0", "Multiply", "Decimal",
+];
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::from_keypad(&QWERTY)
+    }
+}
+
+impl Keymap {
+    pub const LAYOUTS: [&'static str; 4] = ["qwerty", "azerty", "dvorak", "numpad"];
+
+    fn from_keypad(hosts: &[&str; 16]) -> Self {
+        let mut keymap = Keymap {
+            keys: Default::default(),
+        };
+        for (key, host) in KEYPAD.iter().zip(hosts.iter()) {
+            keymap.keys[*key] = vec![host.to_string()];
+        }
+        keymap
+    }
+    // the default mapping of a gamepad: the d-pad and the left stick on
+    // 2/4/6/8, the arrows of most games, and the bottom button on 5
+    pub fn gamepad() -> Self {
+        let mut keymap = Keymap {
+            keys: Default::default(),
+        };
+        let hosts = [
+            (0x2, ["DPadUp", "LeftStickY+"]),
+            (0x4, ["DPadLeft", "LeftStickX-"]),
+            (0x6, ["DPadRight", "LeftStickX+"]),
+            (0x8, ["DPadDown", "LeftStickY-"]),
+        ];
+        for (key, names) in hosts.iter() {
+            keymap.keys[*key] = names.iter().map(|name| name.to_string()).collect();
+        }
+        keymap.keys[0x5] = vec!["South".to_string()];
+        keymap
+    }
+    // a gamepad mapping from what the keys of a rom do, as told by the
+    // rom database: the directions on the d-pad and the left stick (the
+    // right one for the second player), a and b on the bottom and right
+    // buttons. Nothing is mapped for the keys without a hint
+    pub fn from_hints(hints: &[(String, usize)]) -> Self {
+        let mut keymap = Keymap {
+            keys: Default::default(),
+        };
+        for (hint, key) in hints {
+            let hosts: &[&str] = match hint.as_str() {
+                "up" => &["DPadUp", "LeftStickY+"],
+                "down" => &["DPadDown", "LeftStickY-"],
+                "left" => &["DPadLeft", "LeftStickX-"],
+                "right" => &["DPadRight", "LeftStickX+"],
+                "a" => &["South"],
+                "b" => &["East"],
+                "player2Up" => &["RightStickY+"],
+                "player2Down" => &["RightStickY-"],
+                "player2Left" => &["RightStickX-"],
+                "player2Right" => &["RightStickX+"],
+                _ => &[],
+            };
+            keymap.keys[*key].extend(hosts.iter().map(|host| host.to_string()));
+        }
+        keymap
+    }
+    // finds a layout by name, as used in the configuration
+    pub fn layout(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "qwerty" => Some(Keymap::from_keypad(&QWERTY)),
+            "azerty" => Some(Keymap::from_keypad(&AZERTY)),
+            "dvorak" => Some(Keymap::from_keypad(&DVORAK)),
+            "numpad" => Some(Keymap::from_keypad(&NUMPAD)),
+            _ => None,
+        }
+    }
+    // replaces the host keys of a CHIP-8 key, an empty list unmaps it
+    pub fn set(&mut self, key: usize, hosts: Vec<String>) {
+        self.keys[key] = hosts;
+    }
+    pub fn host_keys(&self, key: usize) -> &[String] {
+        &self.keys[key]
+    }
+    // the CHIP-8 keys pressed by a host key, the names are case insensitive
+    pub fn chip8_keys<'a>(&'a self, host: &'a str) -> impl Iterator<Item = usize> + 'a {
+        (0..16).filter(move |key| self.keys[*key].iter().any(|h| h.eq_ignore_ascii_case(host)))
+    }
+}
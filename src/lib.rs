@@ -0,0 +1,30 @@
+// CHIP-8 interpreter core, it has no knowledge of windows, audio or
+// input devices so any frontend (or a test) can drive it
+pub mod asm;
+pub mod config;
+pub mod cpu;
+pub mod database;
+pub mod debugger;
+pub mod disasm;
+pub mod error;
+pub mod gdb;
+pub mod instruction;
+pub mod keymap;
+pub mod octo;
+pub mod quirks;
+pub mod rewind;
+pub mod runner;
+pub mod sha1;
+pub mod snapshot;
+pub mod trace;
+
+pub use cpu::{Chip8, HEIGHT, HIRES_HEIGHT, HIRES_WIDTH, MEMORY_SIZE, WIDTH};
+pub use database::{Database, RomInfo};
+pub use debugger::Debugger;
+pub use error::{AsmError, Chip8Error, ConfigError, DecodeError, RomError, StateError};
+pub use instruction::{decode, Instruction};
+pub use keymap::Keymap;
+pub use quirks::Quirks;
+pub use rewind::Rewind;
+pub use runner::Runner;
+pub use trace::Tracer;
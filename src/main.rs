@@ -1,28 +1,179 @@
 use ::ggez::*;
-use ggez::event::{KeyCode};
-use std::thread;
-use std::time::{Duration, Instant};
+use beeper::{Beeper, NullBeeper, SquareBeeper};
+use chip8::config::Config;
+use chip8::debugger::{self, Command, Stop};
+use chip8::runner::{self, DEFAULT_IPF, FRAME_RATE};
+use chip8::{sha1, Chip8, Chip8Error, Debugger, Keymap, Rewind, Runner, Tracer, HEIGHT, WIDTH};
+use console::Console;
+use ggez::event::{Axis, Button, GamepadId, KeyCode};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, LineWriter, Write};
 
-mod cpu;
+mod beeper;
+mod cli;
+mod console;
 
 // the chip-8 has a screen of 64x32, which is quite small
-// for modern screens, so a ZOOM is applied
-const ZOOM: usize = 20;
-const HEIGHT: usize = 32;
-const WIDTH: usize = 64;
-
+// for modern screens, so it's scaled unless --scale says otherwise
+const DEFAULT_SCALE: usize = 20;
+// a window of 6400x3200 is already larger than any screen
+const MAX_SCALE: usize = 100;
+// the colours of the pixels, indexed by the lit bitplanes: bg is
+// black and fg is white, the others are only used by XO-CHIP roms.
+// The rom database and --palette can give others
+const PALETTE: [(u8, u8, u8); 4] = [(0, 0, 0), (255, 255, 255), (255, 102, 0), (102, 34, 0)];
+// the beep sound unless --pitch and --volume say otherwise, pitch
+// in Hz and volume in percent
+const DEFAULT_PITCH: u32 = 440;
+const DEFAULT_VOLUME: u32 = 25;
+// the pitches people can hear
+const MIN_PITCH: u32 = 20;
+const MAX_PITCH: u32 = 20_000;
+// number of save state slots, picked with F6 and F7
+const STATE_SLOTS: usize = 10;
+// how far back the game can be rewinded, in seconds
+const REWIND_SECONDS: usize = 30;
+// how far a stick has to be pushed to press the key of that direction
+const AXIS_THRESHOLD: f32 = 0.5;
+// the configuration file, looked for in the current directory and
+// then in the user's configuration directory
+const CONFIG_FILE: &str = "chip8.toml";
 
 struct Emulator {
-    chip8: cpu::Chip8,
-    acc_timer: Duration,
+    chip8: Chip8,
+    beeper: Box<dyn Beeper>,
+    // instructions per frame
+    ipf: usize,
+    // the size of a pixel in low resolution
+    scale: usize,
+    // set when the rom crashes, the emulation stops but the
+    // window stays open showing the last frame
+    error: Option<Chip8Error>,
+    // the save states are written next to the rom, one file per slot
+    rom_path: String,
+    slot: usize,
+    // the last frames, played backward while the rewind key is held
+    rewind: Rewind,
+    rewinding: bool,
+    // controlled by the F1/F10/F11 keys and the commands typed in the terminal
+    debugger: Debugger,
+    console: Console,
+    // the host keys of each CHIP-8 key, from the configuration file
+    keymap: Keymap,
+    palette: [(u8, u8, u8); 4],
+    // the same for the gamepads, and the direction each axis is pushed
+    // to ("+", "-" or "" when centered)
+    gamepad: Keymap,
+    axes: HashMap<String, &'static str>,
 }
 impl Emulator {
-    pub fn new() -> Self {
+    pub fn new(chip8: Chip8, beeper: Box<dyn Beeper>, ipf: usize, scale: usize, rom_path: String) -> Self {
         Emulator {
-            chip8: cpu::Chip8::init(),
-            acc_timer: Duration::new(0, 0),
+            chip8,
+            beeper,
+            ipf,
+            scale,
+            error: None,
+            rom_path,
+            slot: 0,
+            rewind: Rewind::new(REWIND_SECONDS * FRAME_RATE as usize),
+            rewinding: false,
+            debugger: Debugger::new(),
+            console: Console::new(),
+            keymap: Keymap::default(),
+            palette: PALETTE,
+            gamepad: Keymap::gamepad(),
+            axes: HashMap::new(),
+        }
+    }
+    fn state_path(&self) -> String {
+        format!("{}.state{}", self.rom_path, self.slot)
+    }
+    fn save_state(&self) {
+        let path = self.state_path();
+        match fs::write(&path, self.chip8.save_state()) {
+            Ok(_) => println!("Saved state to {}", path),
+            Err(e) => eprintln!("{}: {}", path, e),
+        }
+    }
+    fn load_state(&mut self) {
+        let path = self.state_path();
+        let result = fs::read(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| self.chip8.load_state(&data).map_err(|e| e.to_string()));
+        match result {
+            Ok(_) => {
+                println!("Loaded state from {}", path);
+                // a crashed rom can be brought back by loading a state
+                self.error = None;
+                // the recorded frames belong to another timeline
+                self.rewind.clear();
+            }
+            Err(e) => eprintln!("{}: {}", path, e),
+        }
+    }
+    // shows why the debugger stopped the execution, if it did
+    fn report(&mut self, ctx: &mut Context, result: Result<Option<Stop>, Chip8Error>) {
+        match result {
+            Ok(None) => (),
+            Ok(Some(stop)) => {
+                println!("Paused: {}", stop);
+                print!("{}", self.debugger.view(&self.chip8));
+            }
+            Err(e) => {
+                eprintln!("Emulation stopped: {}", e);
+                graphics::set_window_title(ctx, &format!("CHIP-8 - {}", e));
+                print!("{}", self.debugger.view(&self.chip8));
+                self.error = Some(e);
+            }
+        }
+    }
+    fn command(&mut self, ctx: &mut Context, command: Command) {
+        match command {
+            Command::Pause => {
+                self.debugger.pause();
+                print!("{}", self.debugger.view(&self.chip8));
+            }
+            Command::Continue => self.debugger.resume(),
+            Command::Step => {
+                let result = self.debugger.step(&mut self.chip8);
+                self.report(ctx, result);
+                if self.error.is_none() {
+                    print!("{}", self.debugger.view(&self.chip8));
+                }
+            }
+            Command::Frame => {
+                let result = self.debugger.step_frame(&mut self.chip8, self.ipf);
+                let stopped = result.is_err() || matches!(result, Ok(Some(_)));
+                self.report(ctx, result);
+                if !stopped {
+                    self.chip8.tick_timers();
+                    self.rewind.push(&self.chip8);
+                    print!("{}", self.debugger.view(&self.chip8));
+                }
+            }
+            Command::Break(addr) => {
+                let set = self.debugger.toggle_breakpoint(addr);
+                println!("Breakpoint at {:#05X} {}", addr, if set { "added" } else { "removed" });
+            }
+            Command::Watch(watch) => {
+                let set = self.debugger.toggle_watch(watch);
+                println!("Watchpoint on {} {}", watch, if set { "added" } else { "removed" });
+            }
+            Command::View => print!("{}", self.debugger.view(&self.chip8)),
+            Command::Help => println!("{}", debugger::HELP),
         }
     }
+    fn set_pad(&mut self, name: &str, pressed: bool) {
+        for key in self.gamepad.chip8_keys(name) {
+            self.chip8.set_key(key, pressed);
+        }
+    }
+    fn set_slot(&mut self, ctx: &mut Context, slot: usize) {
+        self.slot = slot;
+        graphics::set_window_title(ctx, &format!("CHIP-8 - slot {}", slot));
+    }
 }
 impl event::EventHandler for Emulator {
     // for the loop
@@ -30,63 +181,77 @@ impl event::EventHandler for Emulator {
     // it will get messy
     fn draw(&mut self, ctx: &mut Context) -> GameResult {
         // only draws if it has been asked by an opcode
-        if self.chip8.update_screen == true {
+        if self.chip8.take_screen_update() {
             //graphics::clear(ctx, [0.0, 0.0, 0.0, 1.0].into());
             let mut screen_mesh = graphics::MeshBuilder::new();
-            let final_mesh;
-            // bg is black and fg is white
-            let background = graphics::BLACK;
-            let foreground = graphics::WHITE;
-            let mut color;
-            for i in 0..HEIGHT as usize {
-                for j in 0..WIDTH as usize {
-                    if self.chip8.gfx[i * WIDTH as usize + j] == true {
-                        color = foreground;
-                    } else {
-                        color = background;
-                    }
+            // in high resolution there are twice the pixels, half the size
+            let (width, height) = (self.chip8.width(), self.chip8.height());
+            let size = WIDTH as f32 * self.scale as f32 / width as f32;
+            let gfx = self.chip8.framebuffer();
+            for i in 0..height {
+                for j in 0..width {
+                    let color: graphics::Color = self.palette[gfx[i * width + j] as usize].into();
                     // adds the new rectangle to the screen_mesh
                     screen_mesh.rectangle(
                         graphics::DrawMode::fill(),
-                        graphics::Rect::new(
-                            (j * ZOOM) as f32,
-                            (i * ZOOM) as f32,
-                            ZOOM as f32,
-                            ZOOM as f32,
-                        ),
+                        graphics::Rect::new(j as f32 * size, i as f32 * size, size, size),
                         color,
                     );
                 }
             }       
             // builds the final mesh
-            final_mesh = screen_mesh.build(ctx)?;
+            let final_mesh = screen_mesh.build(ctx)?;
             // draws it to the screen
             graphics::draw(ctx, &final_mesh, graphics::DrawParam::default())?;
             graphics::present(ctx)?;
-            self.chip8.update_screen = false;
             Ok(())
         } else {
             Ok(())
         }
     }
-    fn update(&mut self, _ctx: &mut Context) -> GameResult {
-        // this equals to 500Hz, maybe change to user input in the future
-        let milli = Duration::from_millis(2);
-        let sixty_hz = Duration::from_millis(16);
-        let now = Instant::now();
-        
-        self.chip8.get_opcode();
-        self.chip8.decode_opcode();
-        self.acc_timer += now.elapsed();
-        // the delay timer needs to be decreased at a rate of 60Hz
-        if self.acc_timer >= sixty_hz && self.chip8.delay_timer > 0 {
-            self.acc_timer -= sixty_hz;
-            self.chip8.delay_timer -= 1;
-        }
-        // sleeps for the necessary time - the time it took to process the opcodes
-        let real_milli = milli - now.elapsed();
-        thread::sleep(real_milli);
-        self.acc_timer += real_milli;
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        // ggez keeps track of the time, so this runs as many frames as
+        // needed to keep up with 60 frames per second
+        while let Some(line) = self.console.poll() {
+            match Command::parse(&line) {
+                Ok(command) => self.command(ctx, command),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        while timer::check_update_time(ctx, FRAME_RATE) {
+            // one frame back per frame, so it plays at the normal speed
+            if self.rewinding {
+                if self.rewind.rewind(&mut self.chip8) && self.error.take().is_some() {
+                    graphics::set_window_title(ctx, "CHIP-8");
+                }
+                continue;
+            }
+            if self.error.is_some() || self.debugger.is_paused() {
+                continue;
+            }
+            let result = self.debugger.run_frame(&mut self.chip8, self.ipf);
+            self.report(ctx, result);
+            // stopped in the middle of the frame
+            if self.error.is_some() || self.debugger.is_paused() {
+                continue;
+            }
+            // the timers are decreased once per frame, at 60Hz
+            self.chip8.tick_timers();
+            self.rewind.push(&self.chip8);
+            // the rom exited through 00FD
+            if self.chip8.is_halted() {
+                event::quit(ctx);
+            }
+        }
+        // XO-CHIP roms can replace the beep by their own sound
+        if let Some(pattern) = self.chip8.audio_pattern() {
+            self.beeper.set_pattern(ctx, pattern, self.chip8.audio_rate())?;
+        }
+        // the beep lasts as long as the sound timer is active
+        let running = self.error.is_none() && !self.debugger.is_paused();
+        self.beeper.set_beeping(running && self.chip8.sound_timer() > 0);
+        // gives the cpu back to the os instead of spinning until the next frame
+        timer::yield_now();
         Ok(())
     }
 
@@ -100,55 +265,27 @@ impl event::EventHandler for Emulator {
         _repeat: bool
     ) 
     {
+        // the keypad first, a host key pressing a CHIP-8 key does only that
+        let name = format!("{:?}", keycode);
+        let mut mapped = false;
+        for key in self.keymap.chip8_keys(&name) {
+            self.chip8.set_key(key, true);
+            mapped = true;
+        }
+        if mapped {
+            return;
+        }
         match keycode {
-            KeyCode::Key1 => {
-                self.chip8.key[1] = true;
-            }
-            KeyCode::Key2 => {
-                self.chip8.key[2] = true;
-            }
-            KeyCode::Key3 => {
-                self.chip8.key[3] = true;
-            }
-            KeyCode::Key4 => {
-                self.chip8.key[0xC] = true;
-            }
-            KeyCode::Q  => {
-                self.chip8.key[4] = true;
-            }
-            KeyCode::W => {
-                self.chip8.key[5] = true;
-            }
-            KeyCode::E => {
-                self.chip8.key[6] = true;
-            }
-            KeyCode::R => {
-                self.chip8.key[0xD] = true;
-            }
-            KeyCode::A => {
-                self.chip8.key[7] = true;
-            }
-            KeyCode::S => {
-                self.chip8.key[8] = true;
-            }
-            KeyCode::D => {
-                self.chip8.key[9] = true;
-            }
-            KeyCode::F => {
-                self.chip8.key[0xE] = true;
-            }
-            KeyCode::Z => {
-                self.chip8.key[0xA] = true;
-            }
-            KeyCode::X => {
-                self.chip8.key[0] = true;
-            }
-            KeyCode::C => {
-                self.chip8.key[0xB] = true;
-            }
-            KeyCode::V => {
-                self.chip8.key[0xF] = true;
-            }
+            KeyCode::M => self.beeper.toggle_mute(),
+            KeyCode::Back => self.rewinding = true,
+            KeyCode::F1 if self.debugger.is_paused() => self.command(ctx, Command::Continue),
+            KeyCode::F1 => self.command(ctx, Command::Pause),
+            KeyCode::F10 => self.command(ctx, Command::Step),
+            KeyCode::F11 => self.command(ctx, Command::Frame),
+            KeyCode::F5 => self.save_state(),
+            KeyCode::F9 => self.load_state(),
+            KeyCode::F6 => self.set_slot(ctx, (self.slot + STATE_SLOTS - 1) % STATE_SLOTS),
+            KeyCode::F7 => self.set_slot(ctx, (self.slot + 1) % STATE_SLOTS),
             KeyCode::Escape => event::quit(ctx),
             _ => (),
         }
@@ -161,76 +298,203 @@ impl event::EventHandler for Emulator {
         _keymods: event::KeyMods,
     )
     {
-        match keycode {
-            KeyCode::Key1 => {
-                self.chip8.key[1] = false;
-            }
-            KeyCode::Key2 => {
-                self.chip8.key[2] = false;
-            }
-            KeyCode::Key3 => {
-                self.chip8.key[3] = false;
-            }
-            KeyCode::Key4 => {
-                self.chip8.key[0xC] = false;
-            }
-            KeyCode::Q => {
-                self.chip8.key[4] = false;
-            }
-            KeyCode::W => {
-                self.chip8.key[5] = false;
-            }
-            KeyCode::E => {
-                self.chip8.key[6] = false;
-            }
-            KeyCode::R => {
-                self.chip8.key[0xD] = false;
-            }
-            KeyCode::A => {
-                self.chip8.key[7] = false;
-            }
-            KeyCode::S => {
-                self.chip8.key[8] = false;
-            }
-            KeyCode::D => {
-                self.chip8.key[9] = false;
-            }
-            KeyCode::F => {
-                self.chip8.key[0xE] = false;
-            }
-            KeyCode::Z => {
-                self.chip8.key[0xA] = false;
-            }
-            KeyCode::X => {
-                self.chip8.key[0] = false;
-            }
-            KeyCode::C => {
-                self.chip8.key[0xB] = false;
-            }
-            KeyCode::V => {
-                self.chip8.key[0xF] = false;
-            }
-            _ => (),
+        let name = format!("{:?}", keycode);
+        let mut mapped = false;
+        for key in self.keymap.chip8_keys(&name) {
+            self.chip8.set_key(key, false);
+            mapped = true;
+        }
+        if !mapped && keycode == KeyCode::Back {
+            self.rewinding = false;
+        }
+    }
+    // the buttons and sticks of any gamepad, they all press the same keys
+    fn gamepad_button_down_event(&mut self, _ctx: &mut Context, btn: Button, _id: GamepadId) {
+        self.set_pad(&format!("{:?}", btn), true);
+    }
+    fn gamepad_button_up_event(&mut self, _ctx: &mut Context, btn: Button, _id: GamepadId) {
+        self.set_pad(&format!("{:?}", btn), false);
+    }
+    fn gamepad_axis_event(&mut self, _ctx: &mut Context, axis: Axis, value: f32, _id: GamepadId) {
+        let direction = if value > AXIS_THRESHOLD {
+            "+"
+        } else if value < -AXIS_THRESHOLD {
+            "-"
+        } else {
+            ""
+        };
+        let name = format!("{:?}", axis);
+        let old = self.axes.insert(name.clone(), direction).unwrap_or("");
+        if old == direction {
+            return;
+        }
+        if !old.is_empty() {
+            self.set_pad(&format!("{}{}", name, old), false);
+        }
+        if !direction.is_empty() {
+            self.set_pad(&format!("{}{}", name, direction), true);
         }
     }
 }
 fn main() {
-    // getting the rom path from cmd
-    let path_rom = std::env::args().nth(1).expect("no rom given");
+    let options = cli::parse(std::env::args().skip(1));
+    let path_rom = options.rom.clone();
+
+    let mut chip8 = Chip8::init();
+    if let Some(seed) = options.seed {
+        chip8.seed(seed);
+    }
+    // the one given in the command line has to exist
+    let config = match options.config.clone().or_else(config_path).map(|path| Config::load(&path)) {
+        Some(Ok(config)) => config,
+        Some(Err(e)) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        None => Config::default(),
+    };
+    let database = config.database().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    let platform = options.platform.as_ref().map(|id| {
+        database.platform(id).unwrap_or_else(|| {
+            cli::usage(&format!("unknown platform {}, one of {}", id, database.platform_ids().join(", ")))
+        })
+    });
+    // Octo sources are compiled on the fly, they can't be in the
+    // database. A known rom gets the settings it was written for, the
+    // ones given in the command line still win
+    let loaded = if path_rom.ends_with(".8o") {
+        chip8.load_octo_file(&path_rom).map(|_| None).map_err(|e| e.to_string())
+    } else {
+        chip8
+            .load_rom_with(&path_rom, &database)
+            .map_err(|e| format!("{}: {}", path_rom, e))
+    };
+    let info = match loaded {
+        Ok(info) => info,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let mut title = "CHIP-8".to_string();
+    let mut palette = PALETTE;
+    let mut gamepad = Keymap::gamepad();
+    if let Some(info) = &info {
+        println!("{} ({})", info.title, info.platform);
+        title = format!("CHIP-8 - {}", info.title);
+        for (color, db) in palette.iter_mut().zip(&info.colors) {
+            *color = *db;
+        }
+        if !info.keys.is_empty() {
+            let hints: Vec<String> = info
+                .keys
+                .iter()
+                .map(|(name, key)| format!("{} {:X}", name, key))
+                .collect();
+            println!("Keys: {}", hints.join(", "));
+            gamepad = Keymap::from_hints(&info.keys);
+        }
+    }
+    for (color, option) in palette.iter_mut().zip(&options.palette) {
+        *color = *option;
+    }
+    let quirks = options.quirks.or_else(|| platform.map(|platform| platform.quirks));
+    if let Some(quirks) = quirks {
+        chip8.set_quirks(quirks);
+    }
+    let ipf = options
+        .ipf
+        .or_else(|| platform.and_then(|platform| platform.tickrate))
+        .or_else(|| info.as_ref().and_then(|info| info.tickrate))
+        .unwrap_or(DEFAULT_IPF);
 
-    // window configuration
-    let (mut ctx, mut event_loop) = ContextBuilder::new("CHIP-8", "Vinicius Tikara")
-        .window_setup(ggez::conf::WindowSetup::default().title("CHIP-8"))
-        .window_mode(
-            ggez::conf::WindowMode::default()
-                .dimensions((WIDTH * ZOOM) as f32, (HEIGHT * ZOOM) as f32),
-        )
-        .build()
-        .unwrap();
+    if let Some(path) = &options.trace {
+        // written line by line, so the trace is complete even
+        // when the emulator is killed
+        let output: Box<dyn Write + Send> = if path == "-" {
+            Box::new(io::stdout())
+        } else {
+            match File::create(path) {
+                Ok(file) => Box::new(LineWriter::new(file)),
+                Err(e) => {
+                    eprintln!("{}: {}", path, e);
+                    std::process::exit(1);
+                }
+            }
+        };
+        chip8.set_tracer(Tracer::new(output));
+    }
+
+    if options.headless {
+        // at the speed of the window, until the rom exits or fails
+        let mut runner = Runner::new(ipf);
+        runner.real_time = true;
+        let stop = runner.run(&mut chip8);
+        drop(chip8.take_tracer());
+        if let runner::Stop::Error(e) = stop {
+            eprintln!("Emulation stopped: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // window configuration, if there's no audio device
+    // the emulator runs without sound
+    let context = |audio| {
+        let mut window_mode = ggez::conf::WindowMode::default()
+            .dimensions((WIDTH * options.scale) as f32, (HEIGHT * options.scale) as f32);
+        if options.fullscreen {
+            window_mode = window_mode.fullscreen_type(ggez::conf::FullscreenType::Desktop);
+        }
+        ContextBuilder::new("CHIP-8", "Vinicius Tikara")
+            .window_setup(ggez::conf::WindowSetup::default().title(&title))
+            .window_mode(window_mode)
+            .modules(ggez::conf::ModuleConf::default().audio(audio))
+            .build()
+    };
+    let (mut ctx, mut event_loop, audio) = match context(true) {
+        Ok((ctx, event_loop)) => (ctx, event_loop, true),
+        Err(e) => {
+            eprintln!("Audio disabled: {}", e);
+            let (ctx, event_loop) = context(false).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            (ctx, event_loop, false)
+        }
+    };
+    let beeper: Box<dyn Beeper> = if audio {
+        match SquareBeeper::new(&mut ctx, options.pitch as f32, options.volume as f32 / 100.0, options.mute) {
+            Ok(beeper) => Box::new(beeper),
+            Err(e) => {
+                eprintln!("Audio disabled: {}", e);
+                Box::new(NullBeeper)
+            }
+        }
+    } else {
+        Box::new(NullBeeper)
+    };
     // initialization of the emulator
-    let emulator = &mut Emulator::new();
-    emulator.chip8.load_rom(&path_rom);
-    emulator.chip8.load_font();
+    let emulator = &mut Emulator::new(chip8, beeper, ipf, options.scale, path_rom.clone());
+    emulator.palette = palette;
+
+    let hash = sha1::hex(emulator.chip8.rom_hash());
+    let mappings = config
+        .keymap(&hash, &path_rom)
+        .and_then(|keymap| Ok((keymap, config.gamepad(gamepad, &hash, &path_rom)?)));
+    match mappings {
+        Ok((keymap, gamepad)) => {
+            emulator.keymap = keymap;
+            emulator.gamepad = gamepad;
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
 
     // main loop
     match event::run(&mut ctx, &mut event_loop, emulator) {
@@ -238,3 +502,15 @@ fn main() {
         Err(e) => println!("Error occured: {}", e),
     }
 }
+
+// the first configuration file found, if there's one
+fn config_path() -> Option<String> {
+    let dir = std::env::var("XDG_CONFIG_HOME")
+        .ok()
+        .or_else(|| std::env::var("HOME").ok().map(|home| format!("{}/.config", home)));
+    let mut paths = vec![CONFIG_FILE.to_string()];
+    if let Some(dir) = dir {
+        paths.push(format!("{}/chip8/{}", dir, CONFIG_FILE));
+    }
+    paths.into_iter().find(|path| std::path::Path::new(path).is_file())
+}
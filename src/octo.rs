@@ -0,0 +1,774 @@
+// a compiler for Octo, the high level assembly language most of the
+// modern CHIP-8 programs are written in. It supports the statements
+// printed by the disassembler plus:
+//
+//     : name                    a label, using its name alone calls it
+//     :const NAME value         a constant
+//     :alias name vX            another name for a register
+//     :calc NAME { expr }       a constant computed by an expression
+//     :macro name args { ... }  a macro, expanded by using its name
+//     :byte value, :org addr, :call addr, :unpack n label
+//     if cond then statement, if cond begin ... else ... end
+//     loop ... while cond ... again
+//
+// The conditions are vX == vY/n, vX != vY/n, vX key, vX -key and the
+// comparisons vX < vY/n, >, <= and >=, which like in Octo go through vf.
+// Unless main is the first label of the program, it begins with a jump to it
+use crate::cpu::{Chip8, MEMORY_SIZE};
+use crate::error::AsmError;
+use std::collections::HashMap;
+use std::fs;
+
+// a macro using itself would never stop expanding
+const MAX_EXPANSIONS: usize = 10_000;
+
+// compiles a program into a rom loaded at 0x200
+pub fn compile(source: &str) -> Result<Vec<u8>, AsmError> {
+    compile_named(source, "<source>")
+}
+
+pub fn compile_file(path: &str) -> Result<Vec<u8>, AsmError> {
+    let source = fs::read_to_string(path).map_err(|e| AsmError {
+        file: path.to_string(),
+        line: 0,
+        message: e.to_string(),
+    })?;
+    compile_named(&source, path)
+}
+
+fn compile_named(source: &str, file: &str) -> Result<Vec<u8>, AsmError> {
+    let mut compiler = Compiler::new(source);
+    compiler
+        .program()
+        .map_err(|(line, message)| AsmError {
+            file: file.to_string(),
+            line,
+            message,
+        })?;
+    Ok(compiler.rom)
+}
+
+impl Chip8 {
+    // compiles an Octo program straight into memory, as if it was a rom
+    pub fn load_octo(&mut self, source: &str) -> Result<(), AsmError> {
+        let rom = compile(source)?;
+        self.load_rom_bytes(&rom).expect("the compiler keeps the rom in memory");
+        Ok(())
+    }
+    pub fn load_octo_file(&mut self, path: &str) -> Result<(), AsmError> {
+        let rom = compile_file(path)?;
+        self.load_rom_bytes(&rom).expect("the compiler keeps the rom in memory");
+        Ok(())
+    }
+}
+
+// the errors while compiling, the line and the message
+type Error = (usize, String);
+
+#[derive(Clone)]
+struct Token {
+    text: String,
+    line: usize,
+}
+
+struct Macro {
+    params: Vec<String>,
+    body: Vec<Token>,
+}
+
+// a reference to a label not defined yet, patched at the end
+enum Fixup {
+    // the lower 12 bits of the opcode at the address
+    Nnn(usize),
+    // the 16 bits word at the address
+    Word(usize),
+    // the byte at the address, from the high and the low half of the label
+    High(usize, usize),
+    Low(usize),
+}
+
+// the part of an if or a while deciding if the next instruction runs
+#[derive(Clone, Copy)]
+enum Condition {
+    EqConst(usize, usize),
+    NeConst(usize, usize),
+    Eq(usize, usize),
+    Ne(usize, usize),
+    Key(usize),
+    NotKey(usize),
+}
+
+impl Condition {
+    fn negate(self) -> Condition {
+        match self {
+            Condition::EqConst(x, n) => Condition::NeConst(x, n),
+            Condition::NeConst(x, n) => Condition::EqConst(x, n),
+            Condition::Eq(x, y) => Condition::Ne(x, y),
+            Condition::Ne(x, y) => Condition::Eq(x, y),
+            Condition::Key(x) => Condition::NotKey(x),
+            Condition::NotKey(x) => Condition::Key(x),
+        }
+    }
+    // the skip executing the next instruction only when the condition holds
+    fn skip(self) -> u16 {
+        let op = match self {
+            Condition::EqConst(x, n) => 0x4000 | x << 8 | n,
+            Condition::NeConst(x, n) => 0x3000 | x << 8 | n,
+            Condition::Eq(x, y) => 0x9000 | x << 8 | y << 4,
+            Condition::Ne(x, y) => 0x5000 | x << 8 | y << 4,
+            Condition::Key(x) => 0xE0A1 | x << 8,
+            Condition::NotKey(x) => 0xE09E | x << 8,
+        };
+        op as u16
+    }
+}
+
+struct Compiler {
+    tokens: Vec<Token>,
+    pos: usize,
+    // memory from 0x200, here is where the next byte goes
+    rom: Vec<u8>,
+    here: usize,
+    labels: HashMap<String, usize>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, usize>,
+    macros: HashMap<String, Macro>,
+    fixups: Vec<(Fixup, String, usize)>,
+    // the start of each open loop and the jumps out of it of its whiles
+    loops: Vec<(usize, Vec<usize>)>,
+    // the jump to patch of each open if ... begin
+    ifs: Vec<usize>,
+    expansions: usize,
+    // set once something has been written after the jump to main
+    started: bool,
+}
+
+impl Compiler {
+    fn new(source: &str) -> Self {
+        let mut tokens = Vec::new();
+        for (i, line) in source.lines().enumerate() {
+            for word in line.split_whitespace() {
+                // comments go to the end of the line
+                if word.starts_with('#') {
+                    break;
+                }
+                tokens.push(Token {
+                    text: word.to_string(),
+                    line: i + 1,
+                });
+            }
+        }
+        // the jump to main is there from the start, so nothing
+        // before main moves when it turns out to be needed
+        Compiler {
+            tokens,
+            pos: 0,
+            rom: vec![0x10, 0x00],
+            here: Chip8::START_ADDR + 2,
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            fixups: vec![(Fixup::Nnn(0), "main".to_string(), 0)],
+            loops: Vec::new(),
+            ifs: Vec::new(),
+            expansions: 0,
+            started: false,
+        }
+    }
+
+    fn program(&mut self) -> Result<(), Error> {
+        while self.pos < self.tokens.len() {
+            self.statement()?;
+        }
+        if let Some((start, _)) = self.loops.last() {
+            return Err((0, format!("loop at {:#05X} without again", start)));
+        }
+        if !self.ifs.is_empty() {
+            return Err((0, "if ... begin without end".to_string()));
+        }
+        // an empty program needs no main
+        if !self.started && !self.labels.contains_key("main") {
+            self.rom.clear();
+            self.fixups.clear();
+        }
+        for (fixup, label, line) in std::mem::take(&mut self.fixups) {
+            let addr = *self
+                .labels
+                .get(&label)
+                .ok_or_else(|| match label.as_str() {
+                    "main" => (line, "the program has no main label".to_string()),
+                    _ => (line, format!("undefined label {}", label)),
+                })?;
+            match fixup {
+                Fixup::Nnn(at) => {
+                    if addr > 0xFFF {
+                        return Err((line, format!("{} is too far, use i := long", label)));
+                    }
+                    self.rom[at] = (self.rom[at] & 0xF0) | (addr >> 8) as u8;
+                    self.rom[at + 1] = addr as u8;
+                }
+                Fixup::Word(at) => {
+                    self.rom[at] = (addr >> 8) as u8;
+                    self.rom[at + 1] = addr as u8;
+                }
+                Fixup::High(at, n) => self.rom[at] = (n << 4 | (addr >> 8)) as u8,
+                Fixup::Low(at) => self.rom[at] = addr as u8,
+            }
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Token, Error> {
+        let token = self.tokens.get(self.pos).cloned().ok_or_else(|| {
+            let line = self.tokens.last().map_or(0, |t| t.line);
+            (line, "unexpected end of the program".to_string())
+        })?;
+        self.pos += 1;
+        Ok(token)
+    }
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|t| t.text.as_str())
+    }
+    fn expect(&mut self, text: &str) -> Result<(), Error> {
+        let token = self.next()?;
+        if token.text != text {
+            return Err((token.line, format!("expected {}, found {}", text, token.text)));
+        }
+        Ok(())
+    }
+
+    fn emit(&mut self, byte: u8, line: usize) -> Result<(), Error> {
+        // a missing main is blamed on the first instruction
+        if !self.started {
+            self.started = true;
+            if let Some(jump) = self.fixups.iter_mut().find(|(_, label, _)| label == "main") {
+                jump.2 = line;
+            }
+        }
+        if self.here >= MEMORY_SIZE {
+            return Err((line, "the program doesn't fit in memory".to_string()));
+        }
+        let at = self.here - Chip8::START_ADDR;
+        if self.rom.len() <= at {
+            self.rom.resize(at + 1, 0);
+        }
+        self.rom[at] = byte;
+        self.here += 1;
+        Ok(())
+    }
+    fn op(&mut self, opcode: u16, line: usize) -> Result<(), Error> {
+        self.emit((opcode >> 8) as u8, line)?;
+        self.emit(opcode as u8, line)
+    }
+    // an instruction with a 12 bits address, which may be a label defined later
+    fn jump_to(&mut self, prefix: u16, target: &str, line: usize) -> Result<(), Error> {
+        let at = self.here - Chip8::START_ADDR;
+        match self.address(target, line)? {
+            Some(addr) if addr > 0xFFF => Err((line, format!("{} is too far, use i := long", target))),
+            Some(addr) => self.op(prefix | addr as u16, line),
+            None => {
+                self.fixups.push((Fixup::Nnn(at), target.to_string(), line));
+                self.op(prefix, line)
+            }
+        }
+    }
+    // the value of an address operand, None for a label not defined yet
+    fn address(&self, text: &str, line: usize) -> Result<Option<usize>, Error> {
+        if let Some(addr) = self.labels.get(text) {
+            return Ok(Some(*addr));
+        }
+        match self.number(text) {
+            Some(n) if n < 0.0 || n >= MEMORY_SIZE as f64 => Err((line, format!("invalid address {}", text))),
+            Some(n) => Ok(Some(n as usize)),
+            None if is_name(text) => Ok(None),
+            None => Err((line, format!("invalid address {}", text))),
+        }
+    }
+    // a number literal or a constant
+    fn number(&self, text: &str) -> Option<f64> {
+        if let Some(value) = self.constants.get(text) {
+            return Some(*value);
+        }
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, text),
+        };
+        let value = if let Some(hex) = digits.strip_prefix("0x") {
+            i64::from_str_radix(hex, 16).ok()?
+        } else if let Some(bin) = digits.strip_prefix("0b") {
+            i64::from_str_radix(bin, 2).ok()?
+        } else {
+            digits.parse().ok()?
+        };
+        Some(if negative { -value } else { value } as f64)
+    }
+    // a value that must fit in bits, negative ones are two's complement
+    fn value(&self, token: &Token, bits: u32) -> Result<usize, Error> {
+        let max = (1i64 << bits) - 1;
+        match self.number(&token.text) {
+            Some(n) if (n as i64) <= max && (n as i64) >= -(max + 1) / 2 => Ok((n as i64 & max) as usize),
+            Some(_) => Err((token.line, format!("{} doesn't fit in {} bits", token.text, bits))),
+            None => Err((token.line, format!("{} isn't a number or a constant", token.text))),
+        }
+    }
+    fn register(&self, text: &str) -> Option<usize> {
+        if let Some(x) = self.aliases.get(text) {
+            return Some(*x);
+        }
+        let lower = text.to_lowercase();
+        match lower.strip_prefix('v') {
+            Some(x) if x.len() == 1 => usize::from_str_radix(x, 16).ok(),
+            _ => None,
+        }
+    }
+    fn next_register(&mut self) -> Result<usize, Error> {
+        let token = self.next()?;
+        self.register(&token.text)
+            .ok_or((token.line, format!("{} isn't a register", token.text)))
+    }
+    fn define_label(&mut self, token: &Token) -> Result<(), Error> {
+        if !is_name(&token.text) || self.labels.contains_key(&token.text) {
+            return Err((token.line, format!("invalid or duplicated label {}", token.text)));
+        }
+        // main comes first, the program doesn't need to jump to it
+        if token.text == "main" && self.here == Chip8::START_ADDR + 2 && self.labels.is_empty() && self.loops.is_empty() {
+            self.rom.clear();
+            self.fixups.clear();
+            self.here = Chip8::START_ADDR;
+        }
+        self.labels.insert(token.text.clone(), self.here);
+        Ok(())
+    }
+
+    fn statement(&mut self) -> Result<(), Error> {
+        let token = self.next()?;
+        let line = token.line;
+        if self.register(&token.text).is_some() {
+            self.pos -= 1;
+            return self.register_statement();
+        }
+        match token.text.as_str() {
+            ":" => {
+                let name = self.next()?;
+                self.define_label(&name)?;
+            }
+            ":const" => {
+                let name = self.next()?;
+                let value = self.next()?;
+                let n = self
+                    .number(&value.text)
+                    .or_else(|| self.labels.get(&value.text).map(|a| *a as f64))
+                    .ok_or((value.line, format!("{} isn't a number", value.text)))?;
+                self.constants.insert(name.text, n);
+            }
+            ":alias" => {
+                let name = self.next()?;
+                let x = self.next_register()?;
+                self.aliases.insert(name.text, x);
+            }
+            ":calc" => {
+                let name = self.next()?;
+                let value = self.calc()?;
+                self.constants.insert(name.text, value);
+            }
+            ":macro" => self.define_macro()?,
+            ":byte" => {
+                let value = if self.peek() == Some("{") {
+                    self.calc()? as i64 as usize
+                } else {
+                    let token = self.next()?;
+                    self.value(&token, 8)?
+                };
+                self.emit(value as u8, line)?;
+            }
+            ":org" => {
+                let token = self.next()?;
+                match self.address(&token.text, line)? {
+                    Some(addr) if addr >= Chip8::START_ADDR => self.here = addr,
+                    _ => return Err((line, format!("invalid :org {}", token.text))),
+                }
+            }
+            ":call" => {
+                let target = self.next()?;
+                self.jump_to(0x2000, &target.text, line)?;
+            }
+            ":unpack" => {
+                let n = self.next()?;
+                let n = self.value(&n, 4)?;
+                let label = self.next()?;
+                let (v0, v1) = (self.here - Chip8::START_ADDR + 1, self.here - Chip8::START_ADDR + 3);
+                self.op(0x6000, line)?;
+                self.op(0x6100, line)?;
+                match self.address(&label.text, line)? {
+                    Some(addr) => {
+                        self.rom[v0] = (n << 4 | (addr >> 8)) as u8;
+                        self.rom[v1] = addr as u8;
+                    }
+                    None => {
+                        self.fixups.push((Fixup::High(v0, n), label.text.clone(), line));
+                        self.fixups.push((Fixup::Low(v1), label.text, line));
+                    }
+                }
+            }
+            ":breakpoint" => {
+                self.next()?;
+            }
+            ":monitor" => {
+                self.next()?;
+                self.next()?;
+            }
+            "return" | ";" => self.op(0x00EE, line)?,
+            "clear" => self.op(0x00E0, line)?,
+            "exit" => self.op(0x00FD, line)?,
+            "lores" => self.op(0x00FE, line)?,
+            "hires" => self.op(0x00FF, line)?,
+            "scroll-right" => self.op(0x00FB, line)?,
+            "scroll-left" => self.op(0x00FC, line)?,
+            "audio" => self.op(0xF002, line)?,
+            "scroll-down" | "scroll-up" | "plane" => {
+                let n = self.next()?;
+                let n = self.value(&n, 4)? as u16;
+                let opcode = match token.text.as_str() {
+                    "scroll-down" => 0x00C0 | n,
+                    "scroll-up" => 0x00D0 | n,
+                    _ => 0xF001 | n << 8,
+                };
+                self.op(opcode, line)?;
+            }
+            "bcd" | "saveflags" | "loadflags" => {
+                let x = self.next_register()? as u16;
+                let opcode = match token.text.as_str() {
+                    "bcd" => 0xF033,
+                    "saveflags" => 0xF075,
+                    _ => 0xF085,
+                };
+                self.op(opcode | x << 8, line)?;
+            }
+            "save" | "load" => {
+                let x = self.next_register()? as u16;
+                let store = token.text == "save";
+                if self.peek() == Some("-") {
+                    self.next()?;
+                    let y = self.next_register()? as u16;
+                    self.op(if store { 0x5002 } else { 0x5003 } | x << 8 | y << 4, line)?;
+                } else {
+                    self.op(if store { 0xF055 } else { 0xF065 } | x << 8, line)?;
+                }
+            }
+            "sprite" => {
+                let x = self.next_register()? as u16;
+                let y = self.next_register()? as u16;
+                let n = self.next()?;
+                let n = self.value(&n, 4)? as u16;
+                self.op(0xD000 | x << 8 | y << 4 | n, line)?;
+            }
+            "jump" | "jump0" => {
+                let target = self.next()?;
+                let prefix = if token.text == "jump" { 0x1000 } else { 0xB000 };
+                self.jump_to(prefix, &target.text, line)?;
+            }
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = self.next_register()? as u16;
+                let opcode = match token.text.as_str() {
+                    "delay" => 0xF015,
+                    "buzzer" => 0xF018,
+                    _ => 0xF03A,
+                };
+                self.op(opcode | x << 8, line)?;
+            }
+            "i" => self.i_statement(line)?,
+            "loop" => self.loops.push((self.here, Vec::new())),
+            "while" => {
+                let condition = self.condition()?;
+                let (_, exits) = self.loops.last_mut().ok_or((line, "while outside of a loop".to_string()))?;
+                exits.push(self.here + 2);
+                // jumps out of the loop when the condition doesn't hold
+                self.op(condition.negate().skip(), line)?;
+                self.op(0x1000, line)?;
+            }
+            "again" => {
+                let (start, exits) = self.loops.pop().ok_or((line, "again without loop".to_string()))?;
+                self.op(jump(start, line)?, line)?;
+                for exit in exits {
+                    self.patch_jump(exit, self.here, line)?;
+                }
+            }
+            "if" => {
+                let condition = self.condition()?;
+                let form = self.next()?;
+                match form.text.as_str() {
+                    "then" => {
+                        self.op(condition.skip(), line)?;
+                        self.statement()?;
+                    }
+                    "begin" => {
+                        self.op(condition.negate().skip(), line)?;
+                        self.ifs.push(self.here);
+                        self.op(0x1000, line)?;
+                    }
+                    _ => return Err((form.line, format!("expected then or begin, found {}", form.text))),
+                }
+            }
+            "else" => {
+                let jump = self.ifs.pop().ok_or((line, "else without if ... begin".to_string()))?;
+                self.ifs.push(self.here);
+                self.op(0x1000, line)?;
+                self.patch_jump(jump, self.here, line)?;
+            }
+            "end" => {
+                let jump = self.ifs.pop().ok_or((line, "end without if ... begin".to_string()))?;
+                self.patch_jump(jump, self.here, line)?;
+            }
+            text => {
+                if let Some(n) = self.number(text) {
+                    if !(-128.0..=255.0).contains(&n) {
+                        return Err((line, format!("{} doesn't fit in a byte", text)));
+                    }
+                    self.emit(n as i64 as u8, line)?;
+                } else if self.macros.contains_key(text) {
+                    self.expand(&token)?;
+                } else if is_name(text) {
+                    // a label alone is a call
+                    self.jump_to(0x2000, text, line)?;
+                } else {
+                    return Err((line, format!("unexpected {}", text)));
+                }
+            }
+        }
+        Ok(())
+    }
+    fn patch_jump(&mut self, at: usize, target: usize, line: usize) -> Result<(), Error> {
+        let opcode = jump(target, line)?;
+        let at = at - Chip8::START_ADDR;
+        self.rom[at] = (opcode >> 8) as u8;
+        self.rom[at + 1] = opcode as u8;
+        Ok(())
+    }
+
+    fn register_statement(&mut self) -> Result<(), Error> {
+        let x = self.next_register()? as u16;
+        let op = self.next()?;
+        let line = op.line;
+        let operand = self.next()?;
+        if let Some(y) = self.register(&operand.text) {
+            let y = y as u16;
+            let n = match op.text.as_str() {
+                ":=" => 0,
+                "|=" => 1,
+                "&=" => 2,
+                "^=" => 3,
+                "+=" => 4,
+                "-=" => 5,
+                ">>=" => 6,
+                "=-" => 7,
+                "<<=" => 0xE,
+                _ => return Err((line, format!("unknown operator {}", op.text))),
+            };
+            return self.op(0x8000 | x << 8 | y << 4 | n, line);
+        }
+        let opcode = match (op.text.as_str(), operand.text.as_str()) {
+            (":=", "key") => 0xF00A | x << 8,
+            (":=", "delay") => 0xF007 | x << 8,
+            (":=", "random") => {
+                let mask = self.next()?;
+                0xC000 | x << 8 | self.value(&mask, 8)? as u16
+            }
+            (":=", _) => 0x6000 | x << 8 | self.value(&operand, 8)? as u16,
+            ("+=", _) => 0x7000 | x << 8 | self.value(&operand, 8)? as u16,
+            ("-=", _) => {
+                let n = self.value(&operand, 8)? as u16;
+                0x7000 | x << 8 | (n.wrapping_neg() & 0xFF)
+            }
+            _ => return Err((line, format!("invalid v{:x} {} {}", x, op.text, operand.text))),
+        };
+        self.op(opcode, line)
+    }
+
+    fn i_statement(&mut self, line: usize) -> Result<(), Error> {
+        let op = self.next()?;
+        let operand = self.next()?;
+        match (op.text.as_str(), operand.text.as_str()) {
+            ("+=", _) => {
+                let x = self.register(&operand.text).ok_or((line, format!("{} isn't a register", operand.text)))?;
+                self.op(0xF01E | (x as u16) << 8, line)
+            }
+            (":=", "hex") | (":=", "bighex") => {
+                let x = self.next_register()? as u16;
+                let opcode = if operand.text == "hex" { 0xF029 } else { 0xF030 };
+                self.op(opcode | x << 8, line)
+            }
+            (":=", "long") => {
+                let target = self.next()?;
+                self.op(0xF000, line)?;
+                let at = self.here - Chip8::START_ADDR;
+                match self.address(&target.text, line)? {
+                    Some(addr) => self.op(addr as u16, line),
+                    None => {
+                        self.fixups.push((Fixup::Word(at), target.text, line));
+                        self.op(0, line)
+                    }
+                }
+            }
+            (":=", target) => {
+                let target = target.to_string();
+                self.jump_to(0xA000, &target, line)
+            }
+            _ => Err((line, format!("invalid i {} {}", op.text, operand.text))),
+        }
+    }
+
+    fn condition(&mut self) -> Result<Condition, Error> {
+        let x = self.next_register()?;
+        let op = self.next()?;
+        match op.text.as_str() {
+            "key" => return Ok(Condition::Key(x)),
+            "-key" => return Ok(Condition::NotKey(x)),
+            _ => (),
+        }
+        let operand = self.next()?;
+        let y = self.register(&operand.text);
+        let condition = match (op.text.as_str(), y) {
+            ("==", Some(y)) => Condition::Eq(x, y),
+            ("!=", Some(y)) => Condition::Ne(x, y),
+            ("==", None) => Condition::EqConst(x, self.value(&operand, 8)?),
+            ("!=", None) => Condition::NeConst(x, self.value(&operand, 8)?),
+            ("<", _) | (">", _) | ("<=", _) | (">=", _) => {
+                if x == 0xF || y == Some(0xF) {
+                    return Err((op.line, format!("vf can't be compared with {}", op.text)));
+                }
+                // vf := operand, then a subtraction leaves in vf whether
+                // there was no borrow: vX >= operand for =-, operand >= vX for -=
+                let load = match y {
+                    Some(y) => 0x8F00 | (y as u16) << 4,
+                    None => 0x6F00 | self.value(&operand, 8)? as u16,
+                };
+                self.op(load, op.line)?;
+                let (subtract, holds) = match op.text.as_str() {
+                    ">=" => (0x8F07, 1),
+                    "<" => (0x8F07, 0),
+                    "<=" => (0x8F05, 1),
+                    _ => (0x8F05, 0),
+                };
+                self.op(subtract | (x as u16) << 4, op.line)?;
+                Condition::EqConst(0xF, holds)
+            }
+            _ => return Err((op.line, format!("unsupported condition {}", op.text))),
+        };
+        Ok(condition)
+    }
+
+    fn define_macro(&mut self) -> Result<(), Error> {
+        let name = self.next()?;
+        let mut params = Vec::new();
+        loop {
+            let token = self.next()?;
+            if token.text == "{" {
+                break;
+            }
+            params.push(token.text);
+        }
+        let mut body = Vec::new();
+        let mut depth = 0;
+        loop {
+            let token = self.next()?;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" if depth == 0 => break,
+                "}" => depth -= 1,
+                _ => (),
+            }
+            body.push(token);
+        }
+        self.macros.insert(name.text, Macro { params, body });
+        Ok(())
+    }
+    // replaces the macro and its arguments by its body
+    fn expand(&mut self, name: &Token) -> Result<(), Error> {
+        self.expansions += 1;
+        if self.expansions > MAX_EXPANSIONS {
+            return Err((name.line, format!("too many expansions of {}", name.text)));
+        }
+        let count = self.macros[&name.text].params.len();
+        let mut args = Vec::new();
+        for _ in 0..count {
+            args.push(self.next()?);
+        }
+        let m = &self.macros[&name.text];
+        let body: Vec<Token> = m
+            .body
+            .iter()
+            .map(|token| match m.params.iter().position(|p| *p == token.text) {
+                Some(i) => args[i].clone(),
+                None => token.clone(),
+            })
+            .collect();
+        self.tokens.splice(self.pos..self.pos, body);
+        Ok(())
+    }
+
+    // a { } expression, evaluated from right to left like Octo does
+    fn calc(&mut self) -> Result<f64, Error> {
+        self.expect("{")?;
+        let value = self.calc_expr()?;
+        self.expect("}")?;
+        Ok(value)
+    }
+    fn calc_expr(&mut self) -> Result<f64, Error> {
+        let lhs = self.calc_term()?;
+        let op = match self.peek() {
+            Some(op) if BINARY_OPS.contains(&op) => op.to_string(),
+            _ => return Ok(lhs),
+        };
+        let line = self.next()?.line;
+        let rhs = self.calc_expr()?;
+        let (a, b) = (lhs as i64, rhs as i64);
+        Ok(match op.as_str() {
+            "+" => lhs + rhs,
+            "-" => lhs - rhs,
+            "*" => lhs * rhs,
+            "/" | "%" if rhs == 0.0 => return Err((line, "division by zero".to_string())),
+            "/" => lhs / rhs,
+            "%" => lhs % rhs,
+            "&" => (a & b) as f64,
+            "|" => (a | b) as f64,
+            "^" => (a ^ b) as f64,
+            "<<" => a.checked_shl(b as u32).unwrap_or(0) as f64,
+            _ => a.checked_shr(b as u32).unwrap_or(0) as f64,
+        })
+    }
+    fn calc_term(&mut self) -> Result<f64, Error> {
+        let token = self.next()?;
+        match token.text.as_str() {
+            "(" => {
+                let value = self.calc_expr()?;
+                self.expect(")")?;
+                Ok(value)
+            }
+            "-" => Ok(-self.calc_term()?),
+            "~" => Ok(!(self.calc_term()? as i64) as f64),
+            "HERE" => Ok(self.here as f64),
+            text => self
+                .number(text)
+                .or_else(|| self.labels.get(text).map(|a| *a as f64))
+                .ok_or((token.line, format!("{} isn't a number, a constant or a label", text))),
+        }
+    }
+}
+
+// a 1NNN to the address, which has to fit in the 12 bits
+fn jump(target: usize, line: usize) -> Result<u16, Error> {
+    if target > 0xFFF {
+        return Err((line, format!("{:#X} is too far for a jump", target)));
+    }
+    Ok(0x1000 | target as u16)
+}
+
+const BINARY_OPS: [&str; 10] = ["+", "-", "*", "/", "%", "&", "|", "^", "<<", ">>"];
+
+fn is_name(text: &str) -> bool {
+    let mut chars = text.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
@@ -0,0 +1,86 @@
+// some instructions behave differently between the original COSMAC VIP
+// interpreter and the ones that came after it, and roms were written
+// expecting one or the other. Each flag, when set, turns on the
+// behaviour described next to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    // 8XY6/8XYE shift VX in place, instead of shifting VY into VX
+    pub shift: bool,
+    // FX55/FX65 leave I untouched, instead of incrementing it by X + 1
+    pub load_store: bool,
+    // BNNN jumps to NNN + VX (X being the highest nibble of NNN), instead of NNN + V0
+    pub jump: bool,
+    // DXYN clips sprites at the edges of the screen, instead of wrapping them
+    pub clip: bool,
+    // 8XY1/8XY2/8XY3 reset VF to 0
+    pub vf_reset: bool,
+    // DXYN waits for the next frame, so at most one sprite is drawn per frame
+    pub display_wait: bool,
+}
+
+impl Quirks {
+    // the original interpreter of the COSMAC VIP
+    pub const COSMAC_VIP: Quirks = Quirks {
+        shift: false,
+        load_store: false,
+        jump: false,
+        clip: true,
+        vf_reset: true,
+        display_wait: true,
+    };
+    // the interpreter for the HP-48 calculators. Its FX55/FX65 still
+    // moved I, SUPER-CHIP 1.1 is the one that stopped doing it. It
+    // really added X, one less than the COSMAC VIP, but only the X + 1
+    // of the VIP is emulated
+    pub const CHIP_48: Quirks = Quirks {
+        shift: true,
+        load_store: false,
+        jump: true,
+        clip: true,
+        vf_reset: false,
+        display_wait: false,
+    };
+    // SUPER-CHIP 1.1, also for the HP-48
+    pub const SUPER_CHIP: Quirks = Quirks {
+        shift: true,
+        load_store: true,
+        jump: true,
+        clip: true,
+        vf_reset: false,
+        display_wait: false,
+    };
+    // XO-CHIP, as implemented by Octo
+    pub const XO_CHIP: Quirks = Quirks {
+        shift: false,
+        load_store: false,
+        jump: false,
+        clip: false,
+        vf_reset: false,
+        display_wait: false,
+    };
+
+    // finds a preset by name, as used in the command line
+    pub fn preset(name: &str) -> Option<Quirks> {
+        match name.to_lowercase().as_str() {
+            "vip" | "cosmac" | "cosmac-vip" | "chip-8" | "chip8" => Some(Quirks::COSMAC_VIP),
+            "chip-48" | "chip48" => Some(Quirks::CHIP_48),
+            "schip" | "super-chip" | "superchip" => Some(Quirks::SUPER_CHIP),
+            "xo-chip" | "xochip" => Some(Quirks::XO_CHIP),
+            _ => None,
+        }
+    }
+}
+
+// how this emulator always behaved, which most of the roms in roms/ are fine with
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks {
+            shift: true,
+            load_store: true,
+            jump: false,
+            clip: false,
+            vf_reset: false,
+            display_wait: false,
+        }
+    }
+}
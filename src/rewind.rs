@@ -0,0 +1,124 @@
+// keeps the last frames of the emulation so they can be played backward.
+// Storing a whole save state per frame would take ~4MB per second, so
+// only the newest state is kept whole and each older frame is the
+// difference with the next one (xor), run length encoded. Most of
+// the memory doesn't change between frames so they end up tiny
+use crate::cpu::Chip8;
+use std::collections::VecDeque;
+
+pub struct Rewind {
+    // the newest state, as returned by Chip8::save_state
+    current: Option<Vec<u8>>,
+    // the differences with the previous frames, the newest last
+    deltas: VecDeque<Delta>,
+    // how many frames can be rewinded
+    capacity: usize,
+}
+
+struct Delta {
+    // the length of the older state, states with a XO-CHIP
+    // audio pattern are a bit longer
+    len: usize,
+    data: Vec<u8>,
+}
+
+impl Rewind {
+    // keeps up to the given number of frames, at 60 frames per
+    // second that's 60 times the seconds of rewind wanted
+    pub fn new(capacity: usize) -> Self {
+        Rewind {
+            current: None,
+            deltas: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+    // records the state of the machine, meant to be called once per frame
+    pub fn push(&mut self, chip8: &Chip8) {
+        let state = chip8.save_state();
+        if let Some(current) = self.current.take().filter(|_| self.capacity > 0) {
+            if self.deltas.len() == self.capacity {
+                self.deltas.pop_front();
+            }
+            self.deltas.push_back(Delta {
+                len: current.len(),
+                data: encode(&xor(&current, &state)),
+            });
+        }
+        self.current = Some(state);
+    }
+    // restores the machine to the frame before the newest one recorded,
+    // which is then forgotten. Returns false when there's nothing left
+    pub fn rewind(&mut self, chip8: &mut Chip8) -> bool {
+        let (current, delta) = match (self.current.take(), self.deltas.pop_back()) {
+            (Some(current), Some(delta)) => (current, delta),
+            (current, _) => {
+                self.current = current;
+                return false;
+            }
+        };
+        let mut state = xor(&current, &decode(&delta.data));
+        state.truncate(delta.len);
+        // the states come from save_state so they can't be rejected
+        // unless the rom was changed without calling clear
+        let restored = chip8.load_state(&state).is_ok();
+        self.current = Some(state);
+        restored
+    }
+    // forgets every frame, e.g. after loading a save state
+    pub fn clear(&mut self) {
+        self.current = None;
+        self.deltas.clear();
+    }
+    // the number of frames that can be rewinded
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+    // approximate memory used by the recorded frames, in bytes
+    pub fn size(&self) -> usize {
+        let current = self.current.as_ref().map_or(0, |c| c.len());
+        current + self.deltas.iter().map(|d| d.data.len()).sum::<usize>()
+    }
+}
+
+// the shorter of the two is padded with zeros
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut out = long.to_vec();
+    for (byte, other) in out.iter_mut().zip(short) {
+        *byte ^= other;
+    }
+    out
+}
+
+// the data becomes a list of runs: the number of zeros, the number of
+// other bytes and those bytes. The counts are one byte, a longer run
+// is split in several
+fn encode(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let zeros = data[pos..].iter().take(255).take_while(|b| **b == 0).count();
+        pos += zeros;
+        let literals = data[pos..].iter().take(255).take_while(|b| **b != 0).count();
+        out.push(zeros as u8);
+        out.push(literals as u8);
+        out.extend_from_slice(&data[pos..pos + literals]);
+        pos += literals;
+    }
+    out
+}
+
+fn decode(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos + 1 < data.len() {
+        let (zeros, literals) = (data[pos] as usize, data[pos + 1] as usize);
+        out.resize(out.len() + zeros, 0);
+        out.extend_from_slice(&data[pos + 2..pos + 2 + literals]);
+        pos += 2 + literals;
+    }
+    out
+}
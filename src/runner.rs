@@ -0,0 +1,146 @@
+// runs a rom without a window, a frame of instructions and a timer
+// tick at a time, until something stops it. chip8-headless and the
+// --headless mode of the window both use it
+use crate::cpu::Chip8;
+use crate::error::Chip8Error;
+use std::fmt;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// the rate of the timers, and so of the frames
+pub const FRAME_RATE: u32 = 60;
+// instructions per frame if nothing says otherwise, about the 500
+// instructions a second most roms expect
+pub const DEFAULT_IPF: usize = 500 / FRAME_RATE as usize;
+
+// why the execution stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    Frames(u64),
+    Pc(usize),
+    Opcode(usize),
+    Cycles(u64),
+    Halted,
+    Error(Chip8Error),
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stop::Frames(n) => write!(f, "ran {} frames", n),
+            Stop::Pc(pc) => write!(f, "pc reached {:#05X}", pc),
+            Stop::Opcode(opcode) => write!(f, "executed opcode {:04X}", opcode),
+            Stop::Cycles(n) => write!(f, "ran {} cycles", n),
+            Stop::Halted => write!(f, "rom exited"),
+            Stop::Error(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+// a key held down during some frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Press {
+    pub frame: u64,
+    pub key: usize,
+    pub frames: u64,
+}
+
+// without any of the conditions it runs until the rom exits or fails
+pub struct Runner {
+    // instructions per frame
+    pub ipf: usize,
+    pub frames: Option<u64>,
+    pub until_pc: Option<usize>,
+    pub until_opcode: Option<usize>,
+    pub max_cycles: Option<u64>,
+    // the scripted keys, they only change between frames
+    pub presses: Vec<Press>,
+    // sleeps between the frames to run at the speed of the window
+    pub real_time: bool,
+    frame: u64,
+    cycles: u64,
+}
+
+impl Runner {
+    pub fn new(ipf: usize) -> Self {
+        Runner {
+            ipf,
+            frames: None,
+            until_pc: None,
+            until_opcode: None,
+            max_cycles: None,
+            presses: Vec::new(),
+            real_time: false,
+            frame: 0,
+            cycles: 0,
+        }
+    }
+    // the frames ended so far
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+    // the instructions executed so far
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn run(&mut self, chip8: &mut Chip8) -> Stop {
+        let frame_time = Duration::from_secs(1) / FRAME_RATE;
+        let mut next = Instant::now();
+        // checked before the first instruction, then after each one
+        if self.until_pc == Some(chip8.pc()) {
+            return Stop::Pc(chip8.pc());
+        }
+        if self.max_cycles == Some(self.cycles) {
+            return Stop::Cycles(self.cycles);
+        }
+        loop {
+            if self.frames == Some(self.frame) {
+                return Stop::Frames(self.frame);
+            }
+            for key in 0..16 {
+                let pressed = self
+                    .presses
+                    .iter()
+                    .any(|p| p.key == key && self.frame >= p.frame && self.frame < p.frame + p.frames);
+                chip8.set_key(key, pressed);
+            }
+            // the frames end like in the window, display wait included
+            let mut stop = None;
+            let result = chip8.run_frame_until(self.ipf, |chip8| {
+                self.cycles += 1;
+                stop = self.check(chip8);
+                stop.is_some()
+            });
+            if let Err(e) = result {
+                return Stop::Error(e);
+            }
+            if let Some(stop) = stop {
+                return stop;
+            }
+            chip8.tick_timers();
+            self.frame += 1;
+
+            if self.real_time {
+                next += frame_time;
+                let now = Instant::now();
+                if next > now {
+                    thread::sleep(next - now);
+                }
+            }
+        }
+    }
+    fn check(&self, chip8: &Chip8) -> Option<Stop> {
+        if self.until_opcode == Some(chip8.opcode()) {
+            Some(Stop::Opcode(chip8.opcode()))
+        } else if chip8.is_halted() {
+            Some(Stop::Halted)
+        } else if self.until_pc == Some(chip8.pc()) {
+            Some(Stop::Pc(chip8.pc()))
+        } else if self.max_cycles == Some(self.cycles) {
+            Some(Stop::Cycles(self.cycles))
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,13 @@
+// SHA-1, used to identify roms. It's not used for anything
+// security related, just to tell roms apart
+use ::sha1::{Digest, Sha1};
+
+// the digest of data
+pub fn digest(data: &[u8]) -> [u8; 20] {
+    Sha1::digest(data).into()
+}
+
+// the digest as lowercase hex, the usual way of writing it
+pub fn hex(digest: &[u8; 20]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,56 @@
+// exports the screen as an image. The functions take a framebuffer
+// as returned by Chip8::framebuffer and its width
+use crate::cpu::Chip8;
+
+// the grey of each pixel value in the greyscale formats, so the
+// XO-CHIP colours can be told apart
+const GREYS: [u8; 4] = [0, 255, 170, 85];
+
+// plain (ASCII) PBM, one character per pixel, 1 being lit. Any
+// bitplane lit counts as lit. Being text it's easy to diff
+pub fn to_pbm(framebuffer: &[u8], width: usize) -> Vec<u8> {
+    let height = framebuffer.len() / width;
+    let mut out = format!("P1\n{} {}\n", width, height).into_bytes();
+    for row in framebuffer.chunks(width) {
+        for pixel in row {
+            out.push(if *pixel != 0 { b'1' } else { b'0' });
+        }
+        out.push(b'\n');
+    }
+    out
+}
+
+// binary PGM, one byte per pixel
+pub fn to_pgm(framebuffer: &[u8], width: usize) -> Vec<u8> {
+    let height = framebuffer.len() / width;
+    let mut out = format!("P5\n{} {}\n255\n", width, height).into_bytes();
+    out.extend(framebuffer.iter().map(|pixel| GREYS[*pixel as usize & 0x3]));
+    out
+}
+
+// 8 bits greyscale PNG
+pub fn to_png(framebuffer: &[u8], width: usize) -> Vec<u8> {
+    let height = framebuffer.len() / width;
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let pixels: Vec<u8> = framebuffer.iter().map(|pixel| GREYS[*pixel as usize & 0x3]).collect();
+    // writing to a vector can't fail, and the sizes match
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(&pixels).unwrap();
+    writer.finish().unwrap();
+    out
+}
+
+// the screen of the machine in one of the formats, picked by the
+// extension of path (pbm, pgm or png)
+pub fn export(chip8: &Chip8, path: &str) -> Option<Vec<u8>> {
+    let (framebuffer, width) = (chip8.framebuffer(), chip8.width());
+    match path.rsplit('.').next()?.to_lowercase().as_str() {
+        "pbm" => Some(to_pbm(framebuffer, width)),
+        "pgm" => Some(to_pgm(framebuffer, width)),
+        "png" => Some(to_png(framebuffer, width)),
+        _ => None,
+    }
+}
@@ -0,0 +1,131 @@
+// logs every executed instruction with the state of the machine right
+// before it, one line each, so a run can be diffed against the trace of
+// another emulator:
+//
+//     00000000 0200 6005 LD V0, 0x05          00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 I:0000 SP:0 DT:00 ST:00
+//
+// In ring mode only the last instructions are kept, and they are only
+// written when the execution fails (or when asked with dump)
+use crate::cpu::Chip8;
+use crate::disasm::{self, Syntax};
+use std::collections::VecDeque;
+use std::io::Write;
+
+pub struct Tracer {
+    output: Box<dyn Write + Send>,
+    // only the instructions at these addresses are logged
+    start: usize,
+    end: usize,
+    // the capacity and the last entries, in ring mode
+    ring: Option<(usize, VecDeque<Entry>)>,
+    // instructions executed since the tracer was attached, logged or not
+    cycle: u64,
+}
+
+// the formatting is left for when the line is written, most
+// of the entries of a ring never are
+struct Entry {
+    cycle: u64,
+    pc: usize,
+    opcode: u16,
+    reg: [u8; 16],
+    index: usize,
+    sp: usize,
+    delay_timer: usize,
+    sound_timer: usize,
+}
+
+impl Tracer {
+    // writes each instruction as it's executed
+    pub fn new(output: Box<dyn Write + Send>) -> Self {
+        Tracer {
+            output,
+            start: 0,
+            end: usize::MAX,
+            ring: None,
+            cycle: 0,
+        }
+    }
+    // keeps the last instructions and writes them when the execution fails
+    pub fn ring(output: Box<dyn Write + Send>, capacity: usize) -> Self {
+        Tracer {
+            ring: Some((capacity, VecDeque::with_capacity(capacity))),
+            ..Tracer::new(output)
+        }
+    }
+    // only logs the instructions between both addresses, included
+    pub fn with_range(mut self, start: usize, end: usize) -> Self {
+        self.start = start;
+        self.end = end;
+        self
+    }
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+    // called by the cpu once the opcode at pc is fetched
+    pub(crate) fn record(&mut self, chip8: &Chip8) {
+        let cycle = self.cycle;
+        self.cycle += 1;
+        let pc = chip8.pc();
+        if pc < self.start || pc > self.end {
+            return;
+        }
+        let mut reg = [0; 16];
+        for (r, v) in reg.iter_mut().zip(chip8.registers()) {
+            *r = *v as u8;
+        }
+        let entry = Entry {
+            cycle,
+            pc,
+            opcode: chip8.opcode() as u16,
+            reg,
+            index: chip8.index(),
+            sp: chip8.sp(),
+            delay_timer: chip8.delay_timer(),
+            sound_timer: chip8.sound_timer(),
+        };
+        match &mut self.ring {
+            Some((capacity, entries)) => {
+                if *capacity == 0 {
+                    return;
+                }
+                if entries.len() == *capacity {
+                    entries.pop_front();
+                }
+                entries.push_back(entry);
+            }
+            // a trace that can't be written shouldn't stop the emulation
+            None => {
+                let _ = writeln!(self.output, "{}", entry);
+            }
+        }
+    }
+    // writes the instructions kept by the ring, the oldest first
+    pub fn dump(&mut self) {
+        if let Some((_, entries)) = &mut self.ring {
+            for entry in entries.drain(..) {
+                let _ = writeln!(self.output, "{}", entry);
+            }
+        }
+        let _ = self.output.flush();
+    }
+}
+
+impl std::fmt::Display for Entry {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let reg: Vec<String> = self.reg.iter().map(|v| format!("{:02X}", v)).collect();
+        write!(
+            f,
+            "{:08} {:04X} {:04X} {:<20} {} I:{:04X} SP:{:X} DT:{:02X} ST:{:02X}",
+            self.cycle,
+            self.pc,
+            self.opcode,
+            disasm::mnemonic(self.opcode, Syntax::Cowgod),
+            reg.join(" "),
+            self.index,
+            self.sp,
+            self.delay_timer,
+            self.sound_timer
+        )
+    }
+}
@@ -0,0 +1,81 @@
+// the assembler has to accept everything the disassembler prints
+use chip8::asm::{assemble, assemble_file};
+use chip8::disasm::{self, Syntax};
+use chip8::{decode, Chip8};
+use std::fs;
+use std::path::Path;
+
+#[test]
+fn every_instruction() {
+    for opcode in 0..=0xFFFF {
+        if decode(opcode).is_err() {
+            continue;
+        }
+        let mut source = disasm::mnemonic(opcode, Syntax::Cowgod);
+        // the address of F000 NNNN follows the opcode
+        if opcode == 0xF000 {
+            source += " 0x1234";
+        }
+        let rom = assemble(&source).unwrap_or_else(|e| panic!("{:04X}: {}", opcode, e));
+        assert_eq!(&rom[..2], &opcode.to_be_bytes(), "{}", source);
+    }
+}
+
+#[test]
+fn listings_round_trip() {
+    let roms = Path::new(env!("CARGO_MANIFEST_DIR")).join("roms");
+    for entry in fs::read_dir(roms).unwrap() {
+        let path = entry.unwrap().path();
+        let rom = fs::read(&path).unwrap();
+        let listing = disasm::listing(&rom, 0x200, Syntax::Cowgod);
+        let assembled = assemble(&listing).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        assert!(assembled == rom, "{} changed", path.display());
+    }
+}
+
+#[test]
+fn directives_and_expressions() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("asm");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("sprites.s"), "box: DB 0b11110000, 0x90, $90, #F0\n").unwrap();
+    let main = dir.join("main.s");
+    fs::write(
+        &main,
+        "SPEED EQU (1 << 2) + 1 ; a comment
+         X EQU SPEED * 2 - 1
+         start:  LD V0, SPEED
+                 LD V1, -X
+                 LD I, box
+         loop:   DRW V0, V1, end - box
+                 JP loop
+                 INCLUDE \"sprites.s\"
+         end:    DW start, 'A'
+                 DB \"hi;\", ~0 & 0xFF",
+    )
+    .unwrap();
+    let rom = assemble_file(main.to_str().unwrap()).unwrap();
+    assert_eq!(
+        rom,
+        [
+            0x60, 0x05, 0x61, 0xF7, 0xA2, 0x0A, 0xD0, 0x14, 0x12, 0x06, 0xF0, 0x90, 0x90, 0xF0, 0x02,
+            0x00, 0x00, 0x41, b'h', b'i', b';', 0xFF
+        ]
+    );
+
+    // it's a rom like any other
+    let mut chip8 = Chip8::init();
+    chip8.load_rom_bytes(&rom).unwrap();
+    chip8.run_frame(4).unwrap();
+    assert_eq!(chip8.registers()[0], 5);
+}
+
+#[test]
+fn errors() {
+    let error = |source: &str| assemble(source).unwrap_err().to_string();
+    assert_eq!(error("CLS\nJP nowhere"), "<source>:2: undefined symbol nowhere");
+    assert_eq!(error("LD V0, 0x100"), "<source>:1: 256 doesn't fit in 8 bits");
+    assert_eq!(error("a: CLS\na: CLS"), "<source>:2: a is already defined");
+    assert_eq!(error("A EQU B + 1\nB EQU A\nJP A"), "<source>:3: A is defined in terms of itself");
+    assert_eq!(error("MOV V0, V1"), "<source>:1: invalid instruction MOV V0, V1");
+    assert_eq!(error("ORG 0x100"), "<source>:1: ORG 0x100 is outside of the rom");
+}
@@ -0,0 +1,37 @@
+// the machines the tests start from. Each test file only uses some
+// of them
+#![allow(dead_code)]
+use chip8::{Chip8, Quirks};
+use std::path::Path;
+
+// a machine with the bytes loaded as the rom
+pub fn load(rom: &[u8]) -> Chip8 {
+    let mut chip8 = Chip8::init();
+    chip8.load_rom_bytes(rom).unwrap();
+    chip8
+}
+
+// a machine with one of the roms of roms/, seeded so the roms using
+// random numbers always play the same
+pub fn load_file(name: &str) -> Chip8 {
+    let rom = Path::new(env!("CARGO_MANIFEST_DIR")).join("roms").join(name);
+    let mut chip8 = Chip8::init();
+    chip8.seed(7);
+    chip8.load_rom(rom.to_str().unwrap()).unwrap();
+    chip8
+}
+
+// loads the opcodes as a rom and executes as many of them
+pub fn run(opcodes: &[u16]) -> Chip8 {
+    run_with(Quirks::default(), opcodes)
+}
+
+pub fn run_with(quirks: Quirks, opcodes: &[u16]) -> Chip8 {
+    let mut chip8 = Chip8::with_quirks(quirks);
+    let rom: Vec<u8> = opcodes.iter().flat_map(|op| vec![(op >> 8) as u8, *op as u8]).collect();
+    chip8.load_rom_bytes(&rom).unwrap();
+    for _ in opcodes {
+        chip8.step().unwrap();
+    }
+    chip8
+}
@@ -0,0 +1,110 @@
+// the configuration file and the key mappings read from it
+use chip8::config::{Config, Value};
+use chip8::Keymap;
+
+const CONFIG: &str = r#"
+# the global settings
+[keys]
+layout = "azerty"
+5 = ["Up", "Z"]   # more than one host key
+c = 'Escape'
+
+[gamepad]
+5 = ["South", "East"]
+
+[roms]
+"BRIX.ch8" = { keys = { 4 = "Left", 6 = "Right" }, gamepad = { 4 = "LeftTrigger" } }
+
+[roms.0123456789abcdef0123456789abcdef01234567.keys]
+layout = "numpad"
+"#;
+
+#[test]
+fn values() {
+    let config = Config::parse(
+        "a = 1_000\nb = 0x10\nc = 2.5\nd = [true, false,\n  \"x\\ty\"]\n[t.u]\nv.w = 'raw\\n'",
+        "test.toml",
+    )
+    .unwrap();
+    assert_eq!(config.get("a"), Some(&Value::Integer(1000)));
+    assert_eq!(config.get("b"), Some(&Value::Integer(16)));
+    assert_eq!(config.get("c"), Some(&Value::Float(2.5)));
+    assert_eq!(
+        config.get("d"),
+        Some(&Value::Array(vec![
+            Value::Boolean(true),
+            Value::Boolean(false),
+            Value::String("x\ty".to_string())
+        ]))
+    );
+    assert_eq!(config.get("t.u.v.w").and_then(Value::as_str), Some("raw\\n"));
+}
+
+#[test]
+fn keymaps() {
+    let config = Config::parse(CONFIG, "chip8.toml").unwrap();
+
+    let keymap = config.keymap("ffff", "roms/PONG").unwrap();
+    let keys = |host| keymap.chip8_keys(host).collect::<Vec<_>>();
+    // the layout is AZERTY, with 5 and C moved
+    assert_eq!(keys("A"), [4]);
+    assert_eq!(keys("W"), [0xA]);
+    assert_eq!(keys("up"), [5]);
+    assert_eq!(keys("Z"), [5]);
+    assert_eq!(keys("Escape"), [0xC]);
+    assert!(keys("Key4").is_empty());
+
+    // by file name, on top of the global keys
+    let keymap = config.keymap("ffff", "roms/BRIX.ch8").unwrap();
+    assert_eq!(keymap.host_keys(4), ["Left"]);
+    assert_eq!(keymap.host_keys(5), ["Up", "Z"]);
+
+    // by hash, the layout replaces everything
+    let keymap = config
+        .keymap("0123456789abcdef0123456789abcdef01234567", "game.ch8")
+        .unwrap();
+    assert_eq!(keymap, Keymap::layout("numpad").unwrap());
+
+    // without configuration, the usual QWERTY keys
+    let keymap = Config::default().keymap("ffff", "game.ch8").unwrap();
+    assert_eq!(keymap.host_keys(0xF), ["V"]);
+}
+
+#[test]
+fn gamepads() {
+    let config = Config::parse(CONFIG, "chip8.toml").unwrap();
+    let gamepad = config.gamepad(Keymap::gamepad(), "ffff", "roms/BRIX.ch8").unwrap();
+    let keys = |host| gamepad.chip8_keys(host).collect::<Vec<_>>();
+    assert_eq!(keys("East"), [5]);
+    assert_eq!(keys("LeftTrigger"), [4]);
+    assert!(keys("DPadLeft").is_empty());
+    // the rest is left as in the default profile
+    assert_eq!(keys("LeftStickY+"), [2]);
+    assert_eq!(keys("DPadDown"), [8]);
+
+    // a layout means nothing for a gamepad
+    let config = Config::parse("[gamepad]\nlayout = \"qwerty\"", "chip8.toml").unwrap();
+    let error = config.gamepad(Keymap::gamepad(), "", "game.ch8").unwrap_err().to_string();
+    assert_eq!(error, "chip8.toml:2: layout isn't a key of the keypad");
+}
+
+#[test]
+fn errors() {
+    let error = |source: &str| {
+        Config::parse(source, "chip8.toml")
+            .and_then(|config| config.keymap("", "game.ch8"))
+            .unwrap_err()
+            .to_string()
+    };
+    assert_eq!(error("[keys]\nlayout = \"colemak\""), "chip8.toml:2: unknown keyboard layout colemak");
+    assert_eq!(error("[keys]\n\nG = \"A\""), "chip8.toml:3: G isn't a key of the keypad");
+    assert_eq!(error("[keys]\n1 = 2"), "chip8.toml:2: keys.1 should be a key name or a list of them");
+    assert_eq!(error("[[keys]]\n1 = 'A'"), "chip8.toml:1: keys should be a table");
+    assert_eq!(error("[roms.game]\n\nkeys = 1"), "chip8.toml:3: roms.game.keys should be a table");
+
+    // the syntax errors come from the toml crate, with their line
+    assert_eq!(error("a = 1\na = 2"), "chip8.toml:2: duplicate key `a` in document root");
+    assert!(error("a = \"x").starts_with("chip8.toml:1: "));
+    assert!(error("a = 1 b").starts_with("chip8.toml:1: "));
+    assert!(error("a = 1\n\n[a]").starts_with("chip8.toml:3: "));
+}
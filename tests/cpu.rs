@@ -0,0 +1,256 @@
+// runs small programs on the cpu and checks the registers they leave
+mod common;
+
+use chip8::{Chip8, Quirks, RomError};
+use common::{load, run};
+
+#[test]
+fn arithmetic_wraps_to_a_byte() {
+    // 0 - 1 borrows and leaves 0xFF, the next add doesn't overflow
+    let chip8 = run(&[0x6100, 0x6201, 0x8125, 0x7101]);
+    assert_eq!(chip8.registers()[1], 0x00);
+    assert_eq!(chip8.registers()[0xF], 0);
+
+    let chip8 = run(&[0x6101, 0x6200, 0x8127]);
+    assert_eq!(chip8.registers()[1], 0xFF);
+    assert_eq!(chip8.registers()[0xF], 0);
+
+    let chip8 = run(&[0x61F0, 0x6220, 0x8124]);
+    assert_eq!(chip8.registers()[1], 0x10);
+    assert_eq!(chip8.registers()[0xF], 1);
+
+    // the address of the character of a wrapped register stays in memory,
+    // the font starts at 0x50
+    let chip8 = run(&[0x6100, 0x6201, 0x8125, 0xF11E, 0xF129]);
+    assert_eq!(chip8.index(), 0x50 + 0xFF * 5);
+}
+
+#[test]
+fn no_borrow_when_equal() {
+    let chip8 = run(&[0x6105, 0x6205, 0x8125]);
+    assert_eq!((chip8.registers()[1], chip8.registers()[0xF]), (0, 1));
+    let chip8 = run(&[0x6105, 0x6205, 0x8127]);
+    assert_eq!((chip8.registers()[1], chip8.registers()[0xF]), (0, 1));
+    let chip8 = run(&[0x6106, 0x6205, 0x8125]);
+    assert_eq!((chip8.registers()[1], chip8.registers()[0xF]), (1, 1));
+    let chip8 = run(&[0x6104, 0x6205, 0x8125]);
+    assert_eq!((chip8.registers()[1], chip8.registers()[0xF]), (0xFF, 0));
+}
+
+#[test]
+fn flag_written_after_vf() {
+    // with VF as the destination the flag wins over the result
+    let chip8 = run(&[0x6FF0, 0x6120, 0x8F14]);
+    assert_eq!(chip8.registers()[0xF], 1);
+    let chip8 = run(&[0x6F05, 0x6103, 0x8F15]);
+    assert_eq!(chip8.registers()[0xF], 1);
+    let chip8 = run(&[0x6F05, 0x6103, 0x8F17]);
+    assert_eq!(chip8.registers()[0xF], 0);
+    let chip8 = run(&[0x6F03, 0x8FF6]);
+    assert_eq!(chip8.registers()[0xF], 1);
+}
+
+#[test]
+fn random_covers_every_byte() {
+    let mut chip8 = Chip8::init();
+    chip8.seed(1);
+    chip8.load_rom_bytes(&[0xC0, 0xFF, 0x12, 0x00]).unwrap();
+    let mut seen = [false; 256];
+    for _ in 0..10_000 {
+        chip8.step().unwrap();
+        seen[chip8.registers()[0]] = true;
+        chip8.step().unwrap();
+    }
+    assert!(seen.iter().all(|seen| *seen));
+}
+
+fn lit(chip8: &Chip8) -> Vec<usize> {
+    chip8.framebuffer().iter().enumerate().filter(|(_, pixel)| **pixel != 0).map(|(i, _)| i).collect()
+}
+
+#[test]
+fn superchip_resolution() {
+    // hires, then the 0 of the font at 120, 60
+    let mut chip8 = run(&[0x00FF, 0x6078, 0x613C, 0x6200, 0xF229, 0xD015]);
+    assert!(chip8.is_hires());
+    assert_eq!((chip8.width(), chip8.height()), (128, 64));
+    assert_eq!(chip8.framebuffer().len(), 128 * 64);
+    // the 0 is 5 rows tall, the last one wraps to the top
+    assert_eq!(&lit(&chip8)[..4], &[120, 121, 122, 123]);
+    assert_eq!(&lit(&chip8)[4..8], &[60 * 128 + 120, 60 * 128 + 121, 60 * 128 + 122, 60 * 128 + 123]);
+    assert!(chip8.take_screen_update());
+
+    // going back clears the screen
+    chip8.load_rom_bytes(&[0x00, 0xFE]).unwrap();
+    chip8.set_pc(0x200);
+    chip8.step().unwrap();
+    assert!(!chip8.is_hires());
+    assert_eq!(chip8.framebuffer().len(), 64 * 32);
+    assert!(lit(&chip8).is_empty());
+}
+
+#[test]
+fn superchip_scroll() {
+    // a pixel at 8, 8 moved down 2, right 4 twice and left 4
+    let mut chip8 = Chip8::init();
+    chip8.load_rom_bytes(&[0x60, 0x08, 0xA2, 0x0E, 0xD0, 0x01, 0x00, 0xC2, 0x00, 0xFB, 0x00, 0xFB, 0x00, 0xFC, 0x80]).unwrap();
+    chip8.run_frame(3).unwrap();
+    assert_eq!(lit(&chip8), [8 * 64 + 8]);
+    chip8.step().unwrap();
+    assert_eq!(lit(&chip8), [10 * 64 + 8]);
+    chip8.run_frame(2).unwrap();
+    assert_eq!(lit(&chip8), [10 * 64 + 16]);
+    chip8.step().unwrap();
+    assert_eq!(lit(&chip8), [10 * 64 + 12]);
+}
+
+#[test]
+fn superchip_big_sprites() {
+    // a 16x16 sprite of the 32 bytes after the code
+    let mut rom = vec![0xA2, 0x04, 0xD0, 0x00];
+    rom.extend_from_slice(&[0xFF; 32]);
+    let mut chip8 = Chip8::init();
+    chip8.load_rom_bytes(&rom).unwrap();
+    chip8.run_frame(2).unwrap();
+    let lit = lit(&chip8);
+    assert_eq!(lit.len(), 16 * 16);
+    assert_eq!((lit[15], lit[16]), (15, 64));
+
+    // the big font has 10 bytes per digit, after the small one
+    let chip8 = run(&[0x6003, 0xF030]);
+    assert_eq!(chip8.index(), 0xA0 + 3 * 10);
+}
+
+#[test]
+fn superchip_flags_and_exit() {
+    // v0..v2 saved in the flags, overwritten, then v0..v1 loaded back
+    let chip8 = run(&[0x6001, 0x6102, 0x6203, 0xF275, 0x6000, 0x6100, 0x6200, 0xF185]);
+    assert_eq!(&chip8.registers()[..3], &[1, 2, 0]);
+
+    let mut chip8 = run(&[0x6001, 0x00FD]);
+    assert!(chip8.is_halted());
+    let pc = chip8.pc();
+    chip8.run_frame(10).unwrap();
+    assert_eq!(chip8.pc(), pc);
+}
+
+#[test]
+fn xochip_memory() {
+    // i := long 0xFFF0, v0..v3 saved there, and loaded back backwards
+    let mut chip8 = Chip8::init();
+    chip8.load_rom_bytes(&[
+        0xF0, 0x00, 0xFF, 0xF0, 0x60, 0x01, 0x61, 0x02, 0x62, 0x03, 0x63, 0x04, 0x50, 0x32, 0x53, 0x03,
+    ])
+    .unwrap();
+    chip8.run_frame(7).unwrap();
+    assert_eq!(chip8.pc(), 0x210);
+    assert_eq!(&chip8.memory()[0xFFF0..0xFFF4], &[1, 2, 3, 4]);
+    // I stays where it was
+    assert_eq!(chip8.index(), 0xFFF0);
+    assert_eq!(&chip8.registers()[..4], &[4, 3, 2, 1]);
+}
+
+#[test]
+fn index_wraps_to_16_bits() {
+    // i := long 0xFFFF, v0 := 2, i += v0
+    let rom = [0xF0, 0x00, 0xFF, 0xFF, 0x60, 0x02, 0xF0, 0x1E];
+    let mut chip8 = load(&rom);
+    chip8.run_frame(3).unwrap();
+    assert_eq!(chip8.index(), 1);
+
+    // i := long 0xFFFF, save v0, moving I past the end
+    let mut chip8 = Chip8::with_quirks(Quirks::XO_CHIP);
+    chip8.load_rom_bytes(&[0xF0, 0x00, 0xFF, 0xFF, 0xF0, 0x55]).unwrap();
+    chip8.run_frame(2).unwrap();
+    assert_eq!(chip8.index(), 0);
+}
+
+#[test]
+fn xochip_planes() {
+    // both planes, a sprite of 1 byte per plane, then plane 2 scrolled up 1
+    let mut chip8 = Chip8::init();
+    chip8.load_rom_bytes(&[0x60, 0x02, 0xF3, 0x01, 0xA2, 0x0E, 0xD1, 0x01, 0xF2, 0x01, 0x00, 0xD1, 0x00, 0x00, 0xC0, 0x60]).unwrap();
+    chip8.run_frame(4).unwrap();
+    assert_eq!(&chip8.framebuffer()[2 * 64..2 * 64 + 4], &[1, 3, 2, 0]);
+    chip8.run_frame(2).unwrap();
+    assert_eq!(&chip8.framebuffer()[2 * 64..2 * 64 + 4], &[1, 1, 0, 0]);
+    assert_eq!(&chip8.framebuffer()[64..64 + 4], &[0, 2, 2, 0]);
+
+    // clearing only touches the selected planes
+    chip8.load_rom_bytes(&[0x00, 0xE0]).unwrap();
+    chip8.set_pc(0x200);
+    chip8.step().unwrap();
+    assert_eq!(lit(&chip8), [2 * 64, 2 * 64 + 1]);
+}
+
+#[test]
+fn xochip_audio() {
+    let chip8 = run(&[0x6040, 0xF03A]);
+    assert_eq!(chip8.audio_pattern(), None);
+    assert_eq!(chip8.audio_rate(), 4000.0);
+
+    // the 16 bytes after the code, an octave higher
+    let mut rom = vec![0xA2, 0x08, 0xF0, 0x02, 0x60, 0x70, 0xF0, 0x3A];
+    rom.extend(0..16);
+    let mut chip8 = Chip8::init();
+    chip8.load_rom_bytes(&rom).unwrap();
+    chip8.run_frame(4).unwrap();
+    let pattern: Vec<u8> = (0..16).collect();
+    assert_eq!(&chip8.audio_pattern().unwrap()[..], &pattern[..]);
+    assert_eq!(chip8.audio_rate(), 8000.0);
+}
+
+#[test]
+fn waits_for_a_key_release() {
+    // v3 := key, then v0 += 1
+    let mut chip8 = Chip8::init();
+    chip8.load_rom_bytes(&[0xF3, 0x0A, 0x70, 0x01]).unwrap();
+    chip8.run_frame(10).unwrap();
+    assert!(chip8.is_waiting_key());
+    assert_eq!(chip8.pc(), 0x200);
+
+    // pressed isn't enough, even for several frames
+    chip8.set_key(0xB, true);
+    chip8.run_frame(10).unwrap();
+    chip8.run_frame(10).unwrap();
+    assert!(chip8.is_waiting_key());
+    assert_eq!(chip8.pc(), 0x200);
+
+    // a key pressed later doesn't count either
+    chip8.set_key(0x2, true);
+    chip8.set_key(0xB, false);
+    chip8.step().unwrap();
+    assert!(!chip8.is_waiting_key());
+    assert_eq!(chip8.registers()[3], 0xB);
+    chip8.step().unwrap();
+    assert_eq!(chip8.registers()[0], 1);
+}
+
+#[test]
+fn waits_for_a_key_at_address_0() {
+    // writes v3 := key over the font at 0 and jumps there, the last
+    // opcode only makes run take one more step
+    let chip8 = run(&[0x60F3, 0x610A, 0xA000, 0xF155, 0x1000, 0xF30A]);
+    assert!(chip8.is_waiting_key());
+    assert_eq!(chip8.pc(), 0);
+}
+
+#[test]
+fn roms_that_dont_fit() {
+    let mut chip8 = Chip8::init();
+    let max = Chip8::MAX_ROM_SIZE;
+    assert_eq!(max, 0x10000 - 0x200);
+    assert!(chip8.load_rom_bytes(&vec![0; max]).is_ok());
+    match chip8.load_rom_bytes(&vec![0; max + 1]) {
+        Err(RomError::TooLarge { size, max: limit }) => assert_eq!((size, limit), (max + 1, max)),
+        other => panic!("{:?}", other),
+    }
+    // only one byte past the limit is read from a reader
+    let endless = std::io::repeat(0);
+    match chip8.load_rom_reader(endless) {
+        Err(RomError::TooLarge { size, .. }) => assert_eq!(size, max + 1),
+        other => panic!("{:?}", other),
+    }
+    assert!(chip8.load_rom_reader(&[0x12, 0x00][..]).is_ok());
+    assert!(matches!(chip8.load_rom("roms/MISSING"), Err(RomError::Io(_))));
+}
@@ -0,0 +1,103 @@
+// the rom database, built in and in the format of the community one
+use chip8::{sha1, Chip8, Database, Quirks};
+use std::fs;
+use std::path::Path;
+
+#[test]
+fn knows_every_rom() {
+    let database = Database::builtin();
+    let roms = Path::new(env!("CARGO_MANIFEST_DIR")).join("roms");
+    for entry in fs::read_dir(roms).unwrap() {
+        let path = entry.unwrap().path();
+        let mut chip8 = Chip8::init();
+        let info = chip8
+            .load_rom_with(path.to_str().unwrap(), &database)
+            .unwrap()
+            .unwrap_or_else(|| panic!("{} is unknown", path.display()));
+        assert!(info.tickrate.is_some(), "{}", info.title);
+        assert_eq!(chip8.quirks(), info.quirks, "{}", info.title);
+    }
+
+    // an unknown rom keeps its quirks
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("unknown.ch8");
+    fs::write(&path, [0x12, 0x00]).unwrap();
+    let mut chip8 = Chip8::with_quirks(Quirks::XO_CHIP);
+    assert!(chip8.load_rom_with(path.to_str().unwrap(), &database).unwrap().is_none());
+    assert_eq!(chip8.quirks(), Quirks::XO_CHIP);
+    assert!(database.lookup(chip8.rom_hash()).is_none());
+}
+
+#[test]
+fn community_format() {
+    let hash = sha1::hex(&sha1::digest(b"rom"));
+    let hashes = format!("{{ \"{}\": 1 }}", hash);
+    let programs = format!(
+        r##"[
+            {{ "title": "Other", "roms": {{}} }},
+            {{
+                "title": "Game é",
+                "authors": ["someone"],
+                "roms": {{
+                    "{}": {{
+                        "file": "game.ch8",
+                        "platforms": ["superchip", "xochip"],
+                        "tickrate": 50,
+                        "quirkyPlatforms": {{ "superchip": {{ "shift": false, "wrap": true }} }},
+                        "keys": {{ "up": 5, "a": 10 }},
+                        "colors": {{ "pixels": ["#000000", "#FF8000"], "buzzer": "#FFAA00" }}
+                    }}
+                }}
+            }}
+        ]"##,
+        hash
+    );
+    let platforms = r#"[
+        {
+            "id": "superchip",
+            "name": "Modern SUPER-CHIP",
+            "defaultTickrate": 30,
+            "quirks": { "shift": true, "memoryIncrementByX": false, "memoryLeaveIUnchanged": true,
+                        "wrap": false, "jump": true, "vblank": false, "logic": false }
+        }
+    ]"#;
+    let database = Database::parse(&hashes, &programs, platforms).unwrap();
+    let info = database.lookup(&sha1::digest(b"rom")).unwrap();
+    assert_eq!(info.title, "Game \u{e9}");
+    assert_eq!(info.platform, "superchip");
+    // the platform quirks, with the ones of the rom on top
+    assert_eq!(
+        info.quirks,
+        Quirks {
+            shift: false,
+            load_store: true,
+            jump: true,
+            clip: false,
+            vf_reset: false,
+            display_wait: false,
+        }
+    );
+    assert_eq!(info.tickrate, Some(50));
+    assert_eq!(info.colors, [(0, 0, 0), (255, 128, 0)]);
+    assert_eq!(info.keys, [("a".to_string(), 10), ("up".to_string(), 5)]);
+
+    let platform = database.platform("SUPERCHIP").unwrap();
+    assert_eq!(platform.tickrate, Some(30));
+    assert!(platform.quirks.clip);
+    assert_eq!(database.platform_ids(), ["superchip"]);
+    assert!(database.platform("xochip").is_none());
+
+    let error = Database::parse(&hashes, &programs, "[]").err().unwrap();
+    assert_eq!(error.to_string(), "programs.json:0: Game \u{e9} has an unknown platform superchip");
+    let error = Database::parse("{ \"a\": 1,, }", "[]", "[]").err().unwrap();
+    assert_eq!(error.to_string(), "sha1-hashes.json:1: key must be a string");
+}
+
+#[test]
+fn platforms_match_the_presets() {
+    let database = Database::builtin();
+    let quirks = |id| database.platform(id).unwrap().quirks;
+    assert_eq!(quirks("originalChip8"), Quirks::COSMAC_VIP);
+    assert_eq!(quirks("chip48"), Quirks::CHIP_48);
+    assert_eq!(quirks("superchip"), Quirks::SUPER_CHIP);
+    assert_eq!(quirks("xochip"), Quirks::XO_CHIP);
+}
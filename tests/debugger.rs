@@ -0,0 +1,129 @@
+// breakpoints, watchpoints and the commands typed in the terminal
+mod common;
+
+use chip8::debugger::{Command, Stop, Watch};
+use chip8::{Chip8, Chip8Error, Debugger, MEMORY_SIZE};
+use common::load;
+
+// v0 := 5, i := 0x300, bcd v0, then v1 += 1 forever
+const ROM: [u8; 10] = [0x60, 0x05, 0xA3, 0x00, 0xF0, 0x33, 0x71, 0x01, 0x12, 0x06];
+
+#[test]
+fn breakpoints() {
+    let mut chip8 = load(&ROM);
+    let mut debugger = Debugger::new();
+    assert!(debugger.toggle_breakpoint(0x206));
+    assert_eq!(debugger.run_frame(&mut chip8, 100), Ok(Some(Stop::Breakpoint(0x206))));
+    assert_eq!(chip8.pc(), 0x206);
+    assert!(debugger.is_paused());
+
+    // nothing runs while paused
+    assert_eq!(debugger.run_frame(&mut chip8, 100), Ok(None));
+    assert_eq!(chip8.pc(), 0x206);
+
+    // resuming executes the instruction of the breakpoint, and
+    // stops the next time around
+    debugger.resume();
+    assert_eq!(debugger.run_frame(&mut chip8, 100), Ok(Some(Stop::Breakpoint(0x206))));
+    assert_eq!(chip8.registers()[1], 1);
+
+    // stepping too, even while paused
+    assert_eq!(debugger.step(&mut chip8), Ok(None));
+    assert_eq!(chip8.pc(), 0x208);
+    assert!(debugger.is_paused());
+
+    assert!(!debugger.toggle_breakpoint(0x206));
+    assert_eq!(debugger.breakpoints().count(), 0);
+    debugger.resume();
+    assert_eq!(debugger.run_frame(&mut chip8, 100), Ok(None));
+    assert!(chip8.registers()[1] > 40);
+}
+
+#[test]
+fn watchpoints() {
+    let mut chip8 = load(&ROM);
+    let mut debugger = Debugger::new();
+    for watch in &[Watch::Register(0), Watch::Index, Watch::Memory(0x302), Watch::Register(1)] {
+        assert!(debugger.toggle_watch(*watch));
+    }
+    let mut stops = Vec::new();
+    while let Some(stop) = debugger.run_frame(&mut chip8, 100).unwrap() {
+        stops.push((stop, chip8.pc()));
+        debugger.resume();
+        if stops.len() == 5 {
+            break;
+        }
+    }
+    let changed = |watch, old, new| Stop::Watchpoint { watch, old, new };
+    assert_eq!(
+        stops,
+        [
+            (changed(Watch::Register(0), 0, 5), 0x202),
+            (changed(Watch::Index, 0, 0x300), 0x204),
+            (changed(Watch::Memory(0x302), 0, 5), 0x206),
+            (changed(Watch::Register(1), 0, 1), 0x208),
+            (changed(Watch::Register(1), 1, 2), 0x208),
+        ]
+    );
+    assert_eq!(stops[2].0.to_string(), "[0x302] changed from 00 to 05");
+
+    // only what exists can be watched
+    assert!(!debugger.toggle_watch(Watch::Memory(MEMORY_SIZE)));
+    assert!(!debugger.toggle_watch(Watch::Register(0x10)));
+    assert!(!debugger.toggle_watch(Watch::Register(1)));
+    assert_eq!(debugger.watches(), [Watch::Register(0), Watch::Index, Watch::Memory(0x302)]);
+}
+
+#[test]
+fn errors_pause() {
+    let mut chip8 = Chip8::init();
+    chip8.load_rom_bytes(&[0x51, 0x21]).unwrap();
+    let mut debugger = Debugger::new();
+    let result = debugger.run_frame(&mut chip8, 10);
+    assert_eq!(result, Err(Chip8Error::UnknownOpcode { pc: 0x200, opcode: 0x5121 }));
+    assert!(debugger.is_paused());
+}
+
+#[test]
+fn view() {
+    let mut chip8 = load(&ROM);
+    let mut debugger = Debugger::new();
+    debugger.toggle_breakpoint(0x206);
+    debugger.run_frame(&mut chip8, 100).unwrap();
+    let view = debugger.view(&chip8);
+    let code: Vec<&str> = view.lines().skip(4).collect();
+    // 5 instructions before pc and 6 from it
+    assert_eq!(code.len(), 11);
+    assert!(code[0].starts_with("   0x1FC: 0000"), "{}", view);
+    assert!(code[5].starts_with(">* 0x206: 7101"), "{}", view);
+    assert!(view.starts_with("v0: 05 v1: 00"), "{}", view);
+
+    // at the start of memory there's nothing before
+    let mut chip8 = Chip8::init();
+    chip8.set_pc(0x4);
+    let view = Debugger::new().view(&chip8);
+    let code: Vec<&str> = view.lines().skip(4).collect();
+    assert!(code[0].starts_with("   0x000:"), "{}", view);
+    assert!(code[2].starts_with(">  0x004:"), "{}", view);
+}
+
+#[test]
+fn commands() {
+    let parse = |line| Command::parse(line);
+    assert_eq!(parse("p"), Ok(Command::Pause));
+    assert_eq!(parse(" c "), Ok(Command::Continue));
+    assert_eq!(parse("s"), Ok(Command::Step));
+    assert_eq!(parse("f"), Ok(Command::Frame));
+    assert_eq!(parse("b 0x2A0"), Ok(Command::Break(0x2A0)));
+    assert_eq!(parse("b 512"), Ok(Command::Break(0x200)));
+    assert_eq!(parse("w i"), Ok(Command::Watch(Watch::Index)));
+    assert_eq!(parse("w vA"), Ok(Command::Watch(Watch::Register(0xA))));
+    assert_eq!(parse("w 0x300"), Ok(Command::Watch(Watch::Memory(0x300))));
+    assert_eq!(parse("v"), Ok(Command::View));
+    assert_eq!(parse("h"), Ok(Command::Help));
+
+    assert_eq!(parse("b"), Err("unknown command b, h for help".to_string()));
+    assert_eq!(parse("b 0xZZ"), Err("invalid number 0xZZ".to_string()));
+    assert_eq!(parse("w vG"), Err("invalid register vG".to_string()));
+    assert_eq!(parse("x 1"), Err("unknown command x 1, h for help".to_string()));
+}
@@ -0,0 +1,34 @@
+// checks the decoder against the disassembler and the cpu, the three of
+// them have to agree on what every opcode means
+use chip8::disasm::{self, Syntax};
+use chip8::{decode, Chip8, Chip8Error, DecodeError, Instruction};
+
+#[test]
+fn decodes_operands() {
+    assert_eq!(decode(0x00E0), Ok(Instruction::Clear));
+    assert_eq!(decode(0x1234), Ok(Instruction::Jump(0x234)));
+    assert_eq!(decode(0x8AB4), Ok(Instruction::Add { x: 0xA, y: 0xB }));
+    assert_eq!(decode(0xD125), Ok(Instruction::Draw { x: 1, y: 2, n: 5 }));
+    assert_eq!(decode(0xF000), Ok(Instruction::LongI));
+    assert_eq!(decode(0xF365), Ok(Instruction::Load(3)));
+    assert_eq!(decode(0x5121), Err(DecodeError { opcode: 0x5121 }));
+    assert_eq!(decode(0x0123), Err(DecodeError { opcode: 0x0123 }));
+}
+
+#[test]
+fn every_opcode() {
+    for opcode in 0..=0xFFFF {
+        let decoded = decode(opcode);
+        // the disassembler only falls back to data for invalid opcodes
+        let text = disasm::mnemonic(opcode, Syntax::Cowgod);
+        assert_eq!(decoded.is_err(), text.starts_with("DW"), "{:04X}", opcode);
+
+        // and the cpu only rejects those
+        let mut chip8 = Chip8::init();
+        chip8
+            .load_rom_bytes(&[(opcode >> 8) as u8, opcode as u8, 0, 0])
+            .unwrap();
+        let unknown = matches!(chip8.step(), Err(Chip8Error::UnknownOpcode { .. }));
+        assert_eq!(decoded.is_err(), unknown, "{:04X}", opcode);
+    }
+}
@@ -0,0 +1,109 @@
+// talks to the stub like gdb would, over a real connection
+use chip8::gdb::GdbStub;
+use chip8::Chip8;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+struct Client {
+    stream: TcpStream,
+}
+
+impl Client {
+    // sends a packet and returns the data of the reply
+    fn send(&mut self, data: &str) -> String {
+        let sum = data.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+        write!(self.stream, "${}#{:02x}", data, sum).unwrap();
+        let mut ack = [0];
+        self.stream.read_exact(&mut ack).unwrap();
+        assert_eq!(ack[0], b'+', "{}", data);
+        self.reply()
+    }
+    fn reply(&mut self) -> String {
+        let mut reply = Vec::new();
+        let mut byte = [0];
+        loop {
+            self.stream.read_exact(&mut byte).unwrap();
+            if byte[0] == b'#' {
+                break;
+            }
+            reply.push(byte[0]);
+        }
+        let mut checksum = [0; 2];
+        self.stream.read_exact(&mut checksum).unwrap();
+        self.stream.write_all(b"+").unwrap();
+        let reply = String::from_utf8(reply).unwrap();
+        assert!(reply.starts_with('$'), "{}", reply);
+        reply[1..].to_string()
+    }
+}
+
+// V0 := 5, then V1 += 1 forever, and a broken instruction
+const ROM: [u8; 8] = [0x60, 0x05, 0x71, 0x01, 0x12, 0x02, 0x51, 0x21];
+
+fn connect() -> (Client, thread::JoinHandle<Chip8>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let mut chip8 = Chip8::init();
+        chip8.load_rom_bytes(&ROM).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        GdbStub::new(10).serve(&mut chip8, stream).unwrap();
+        chip8
+    });
+    let stream = TcpStream::connect(addr).unwrap();
+    stream.set_nodelay(true).unwrap();
+    (Client { stream }, server)
+}
+
+#[test]
+fn session() {
+    let (mut gdb, server) = connect();
+    assert!(gdb.send("qSupported:swbreak+").contains("qXfer:features:read+"));
+    assert!(gdb.send("qXfer:features:read:target.xml:0,1000").starts_with("l<?xml"));
+    assert_eq!(gdb.send("?"), "S05");
+
+    // V0-VF, I, PC (little endian), SP, DT, ST
+    let regs = format!("{}0000{}000000", "00".repeat(16), "0002");
+    assert_eq!(gdb.send("g"), regs);
+    assert_eq!(gdb.send("s"), "S05");
+    assert_eq!(gdb.send("p0"), "05");
+    assert_eq!(gdb.send("p11"), "0202");
+
+    assert_eq!(gdb.send("m200,4"), "60057101");
+    assert_eq!(gdb.send("M300,2:abcd"), "OK");
+    assert_eq!(gdb.send("m300,2"), "abcd");
+
+    // the loop stops on the breakpoint every time around
+    assert_eq!(gdb.send("Z0,202,2"), "OK");
+    assert_eq!(gdb.send("c"), "T05swbreak:;");
+    assert_eq!(gdb.send("c"), "T05swbreak:;");
+    assert_eq!(gdb.send("p1"), "01");
+    assert_eq!(gdb.send("z0,202,2"), "OK");
+
+    // registers can be written too, here to jump to the broken instruction
+    assert_eq!(gdb.send("P11=0602"), "OK");
+    assert_eq!(gdb.send("P1=ff"), "OK");
+    let reply = gdb.send("c");
+    assert!(reply.starts_with('O'), "{}", reply);
+    assert_eq!(gdb.reply(), "S04");
+
+    assert_eq!(gdb.send("D"), "OK");
+    let chip8 = server.join().unwrap();
+    assert_eq!(chip8.registers()[1], 0xFF);
+    assert_eq!(chip8.memory()[0x301], 0xCD);
+}
+
+#[test]
+fn oversized_memory_packets() {
+    let (mut gdb, server) = connect();
+    assert_eq!(gdb.send("m200,ffffffffffffffff"), "E01");
+    assert_eq!(gdb.send("mffffffffffffffff,2"), "E01");
+    assert_eq!(gdb.send("Mffffffffffffffff,2:abcd"), "E01");
+    assert_eq!(gdb.send("M200,ffffffffffffffff:abcd"), "E01");
+    // reads past the end of memory are cut short, the stub is still there
+    assert_eq!(gdb.send("mfffe,4"), "0000");
+    assert_eq!(gdb.send("m200,2"), "6005");
+    assert_eq!(gdb.send("D"), "OK");
+    server.join().unwrap();
+}
@@ -0,0 +1,67 @@
+// runs every rom in roms/ for a few seconds, with the quirks and the
+// speed the database has for it, and compares the screen against the
+// images in tests/golden. To accept new images run:
+//
+//     CHIP8_UPDATE_GOLDEN=1 cargo test --test golden
+use chip8::{snapshot, Chip8, Database};
+use std::fs;
+use std::path::Path;
+
+// 3 seconds
+const FRAMES: usize = 180;
+const SEED: u64 = 0xC8;
+
+fn run(database: &Database, rom: &Path) -> Vec<u8> {
+    let mut chip8 = Chip8::init();
+    chip8.seed(SEED);
+    let info = chip8.load_rom_with(rom.to_str().unwrap(), database).unwrap().unwrap();
+    let ipf = info.tickrate.unwrap();
+    for _ in 0..FRAMES {
+        chip8.run_frame(ipf).unwrap();
+        chip8.tick_timers();
+    }
+    snapshot::to_pbm(chip8.framebuffer(), chip8.width())
+}
+
+#[test]
+fn golden_images() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let update = std::env::var_os("CHIP8_UPDATE_GOLDEN").is_some();
+    let failures_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    let database = Database::builtin();
+
+    let mut roms: Vec<_> = fs::read_dir(root.join("roms"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    roms.sort();
+
+    let mut failures = Vec::new();
+    for rom in roms {
+        let name = rom.file_name().unwrap().to_str().unwrap().to_string();
+        let golden = root.join("tests/golden").join(format!("{}.pbm", name));
+        let image = run(&database, &rom);
+        // a blank screen means the rom didn't get to draw anything,
+        // which isn't worth comparing
+        if !image.split(|b| *b == b'\n').skip(2).flatten().any(|pixel| *pixel == b'1') {
+            failures.push(format!("{}: nothing drawn", name));
+            continue;
+        }
+        if update {
+            fs::write(&golden, &image).unwrap();
+            continue;
+        }
+        match fs::read(&golden) {
+            Ok(expected) if expected == image => (),
+            Ok(_) => {
+                // keeps the screen for comparison
+                fs::create_dir_all(&failures_dir).unwrap();
+                let actual = failures_dir.join(format!("{}.pbm", name));
+                fs::write(&actual, &image).unwrap();
+                failures.push(format!("{}: differs, got {}", name, actual.display()));
+            }
+            Err(_) => failures.push(format!("{}: missing {}", name, golden.display())),
+        }
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
@@ -0,0 +1,153 @@
+// compiles Octo programs, including the listings of the disassembler
+use chip8::disasm::{self, Syntax};
+use chip8::octo::compile;
+use chip8::Chip8;
+use std::fs;
+use std::path::Path;
+
+#[test]
+fn listings_round_trip() {
+    let roms = Path::new(env!("CARGO_MANIFEST_DIR")).join("roms");
+    for entry in fs::read_dir(roms).unwrap() {
+        let path = entry.unwrap().path();
+        let rom = fs::read(&path).unwrap();
+        let listing = disasm::listing(&rom, 0x200, Syntax::Octo);
+        let compiled = compile(&listing).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        assert!(compiled == rom, "{} changed", path.display());
+    }
+}
+
+#[test]
+fn statements() {
+    let source = "
+        :alias counter v3
+        :const LIMIT 10
+        :calc DOUBLE { LIMIT * 2 }
+        :macro twice reg { reg += 1 reg += 1 }
+
+        : main
+            counter := 0
+            loop
+                twice counter
+                while counter != DOUBLE
+            again
+            if counter == 20 begin
+                v4 := 1
+            else
+                v4 := 2
+            end
+            if v4 != 1 then v5 := 7
+            i := sprite
+            sprite v0 v1 2
+            add-ten
+            : halt jump halt
+
+        : add-ten v6 += LIMIT ;
+        : sprite 0x3C 0b01000010
+    ";
+    let rom = compile(source).unwrap();
+    assert_eq!(
+        rom,
+        [
+            0x63, 0x00, // counter := 0
+            0x73, 0x01, 0x73, 0x01, // twice counter
+            0x43, 0x14, 0x12, 0x0C, 0x12, 0x02, // while ... again
+            0x33, 0x14, 0x12, 0x14, 0x64, 0x01, 0x12, 0x16, 0x64, 0x02, // if ... end
+            0x34, 0x01, 0x65, 0x07, // if ... then
+            0xA2, 0x26, 0xD0, 0x12, 0x22, 0x22, 0x12, 0x20, // i, sprite, call, jump
+            0x76, 0x0A, 0x00, 0xEE, // add-ten
+            0x3C, 0x42, // sprite
+        ]
+    );
+
+    let mut chip8 = Chip8::init();
+    chip8.load_octo(source).unwrap();
+    chip8.run_frame(100).unwrap();
+    let regs = chip8.registers();
+    assert_eq!((regs[3], regs[4], regs[5], regs[6]), (20, 1, 0, 10));
+}
+
+#[test]
+fn errors() {
+    let error = |source: &str| compile(source).unwrap_err().to_string();
+    assert_eq!(error("v0 := 1"), "<source>:1: the program has no main label");
+    assert_eq!(error(": main\njump nowhere"), "<source>:2: undefined label nowhere");
+    assert_eq!(error(": main\nv0 := 256"), "<source>:2: 256 doesn't fit in 8 bits");
+    assert_eq!(error(": main\nloop\nv0 += 1"), "<source>:0: loop at 0x200 without again");
+    assert_eq!(error(": main\nif v0 =< v1 then exit"), "<source>:2: unsupported condition =<");
+    assert_eq!(error(": main\nif vf < 3 then exit"), "<source>:2: vf can't be compared with <");
+    assert!(compile("").unwrap().is_empty());
+}
+
+#[test]
+fn code_before_main() {
+    // the jump to main is at 0x200, whatever comes before main follows it
+    assert_eq!(
+        compile(": draw v0 := 1 return : main draw loop again").unwrap(),
+        [0x12, 0x06, 0x60, 0x01, 0x00, 0xEE, 0x22, 0x02, 0x12, 0x08]
+    );
+    assert_eq!(
+        compile("loop v0 += 1 again : main").unwrap(),
+        [0x12, 0x06, 0x70, 0x01, 0x12, 0x02]
+    );
+    assert_eq!(
+        compile("jump later : later : main clear").unwrap(),
+        [0x12, 0x04, 0x12, 0x04, 0x00, 0xE0]
+    );
+    assert_eq!(
+        compile(":unpack 0xA data : data 0x3C : main").unwrap(),
+        [0x12, 0x07, 0x60, 0xA2, 0x61, 0x06, 0x3C]
+    );
+    // unless main is first
+    assert_eq!(compile(":const N 1 : main v0 := N").unwrap(), [0x60, 0x01]);
+}
+
+#[test]
+fn jumps_out_of_reach() {
+    let error = |source: &str| compile(source).unwrap_err().to_string();
+    assert_eq!(error(": main :org 0x1234 loop again"), "<source>:1: 0x1234 is too far for a jump");
+    assert_eq!(
+        error(": main :org 0xFFC\nloop\nwhile v0 != 0\nagain"),
+        "<source>:4: 0x1002 is too far for a jump"
+    );
+    assert_eq!(
+        error(": main :org 0xFFA\nif v0 == 0 begin\nclear\nend"),
+        "<source>:4: 0x1000 is too far for a jump"
+    );
+    // right below the limit it still works
+    let rom = compile(": main :org 0xFFE loop again").unwrap();
+    assert_eq!(&rom[rom.len() - 2..], &[0x1F, 0xFE]);
+}
+
+#[test]
+fn comparisons() {
+    assert_eq!(
+        compile(": main if v1 < v2 then v0 := 1 if v1 >= 5 then v0 := 2").unwrap(),
+        [
+            0x8F, 0x20, 0x8F, 0x17, 0x4F, 0x00, 0x60, 0x01, // vf := v2, vf =- v1, vf == 0
+            0x6F, 0x05, 0x8F, 0x17, 0x4F, 0x01, 0x60, 0x02, // vf := 5, vf =- v1, vf == 1
+        ]
+    );
+    // counts how many of the comparisons hold, for values below, equal and above
+    let source = "
+        : main
+            v0 := 0
+            if v1 < v2 then v0 += 1
+            if v1 > v2 then v0 += 2
+            if v1 <= v2 then v0 += 4
+            if v1 >= v2 then v0 += 8
+            if v1 < 7 then v0 += 16
+            if v1 > 7 then v0 += 32
+            if v1 <= 7 then v0 += 64
+            if v1 >= 7 then v0 += 128
+            exit
+    ";
+    for (v1, expected) in &[(3, 1 | 4 | 16 | 64), (7, 4 | 8 | 64 | 128), (9, 2 | 8 | 32 | 128)] {
+        let mut chip8 = Chip8::init();
+        chip8.load_octo(source).unwrap();
+        chip8.set_register(1, *v1);
+        chip8.set_register(2, 7);
+        chip8.run_frame(100).unwrap();
+        assert_eq!(chip8.registers()[0], *expected, "v1 = {}", v1);
+    }
+}
@@ -0,0 +1,108 @@
+// each quirk changes one instruction, and the presets tell the
+// interpreters apart
+mod common;
+
+use chip8::{Chip8, Quirks, WIDTH};
+use common::run_with;
+
+fn with(change: impl Fn(&mut Quirks)) -> Quirks {
+    let mut quirks = Quirks::default();
+    change(&mut quirks);
+    quirks
+}
+
+#[test]
+fn presets() {
+    assert_eq!(Quirks::preset("vip"), Some(Quirks::COSMAC_VIP));
+    assert_eq!(Quirks::preset("CHIP-8"), Some(Quirks::COSMAC_VIP));
+    assert_eq!(Quirks::preset("chip48"), Some(Quirks::CHIP_48));
+    assert_eq!(Quirks::preset("SuperChip"), Some(Quirks::SUPER_CHIP));
+    assert_eq!(Quirks::preset("xo-chip"), Some(Quirks::XO_CHIP));
+    assert_eq!(Quirks::preset("chip-9"), None);
+
+    let presets = [Quirks::COSMAC_VIP, Quirks::CHIP_48, Quirks::SUPER_CHIP, Quirks::XO_CHIP];
+    for (i, a) in presets.iter().enumerate() {
+        for b in &presets[i + 1..] {
+            assert_ne!(a, b);
+        }
+    }
+    // SUPER-CHIP stopped moving I on FX55/FX65
+    assert_eq!(
+        Quirks::CHIP_48,
+        Quirks {
+            load_store: false,
+            ..Quirks::SUPER_CHIP
+        }
+    );
+}
+
+#[test]
+fn shift() {
+    // v1 := 3, v2 := 8, v1 >>= v2
+    let chip8 = run_with(with(|q| q.shift = true), &[0x6103, 0x6208, 0x8126]);
+    assert_eq!((chip8.registers()[1], chip8.registers()[0xF]), (1, 1));
+    let chip8 = run_with(with(|q| q.shift = false), &[0x6103, 0x6208, 0x8126]);
+    assert_eq!((chip8.registers()[1], chip8.registers()[0xF]), (4, 0));
+    let chip8 = run_with(with(|q| q.shift = false), &[0x6103, 0x6281, 0x812E]);
+    assert_eq!((chip8.registers()[1], chip8.registers()[0xF]), (2, 1));
+}
+
+#[test]
+fn load_store() {
+    // i := 0x300, save v2, load v1
+    let opcodes = [0xA300, 0xF255, 0xF165];
+    assert_eq!(run_with(with(|q| q.load_store = true), &opcodes).index(), 0x300);
+    assert_eq!(run_with(with(|q| q.load_store = false), &opcodes).index(), 0x305);
+}
+
+#[test]
+fn jump() {
+    // v0 := 2, v3 := 4, jump0 0x310
+    let chip8 = run_with(with(|q| q.jump = false), &[0x6002, 0x6304, 0xB310]);
+    assert_eq!(chip8.pc(), 0x312);
+    let chip8 = run_with(with(|q| q.jump = true), &[0x6002, 0x6304, 0xB310]);
+    assert_eq!(chip8.pc(), 0x314);
+}
+
+#[test]
+fn vf_reset() {
+    // vf := 1, v1 |= v2
+    assert_eq!(run_with(with(|q| q.vf_reset = true), &[0x6F01, 0x8121]).registers()[0xF], 0);
+    assert_eq!(run_with(with(|q| q.vf_reset = false), &[0x6F01, 0x8122]).registers()[0xF], 1);
+}
+
+#[test]
+fn clip() {
+    // draws the 0 of the font, 4 pixels wide, at x = 62
+    let draw = |quirks| {
+        let mut chip8 = Chip8::with_quirks(quirks);
+        chip8.load_rom_bytes(&[0x60, 0x3E, 0x61, 0x00, 0xF1, 0x29, 0xD0, 0x15]).unwrap();
+        chip8.run_frame(4).unwrap();
+        chip8
+    };
+    let chip8 = draw(with(|q| q.clip = true));
+    assert_eq!(&chip8.framebuffer()[62..64], &[1, 1]);
+    assert_eq!(&chip8.framebuffer()[..2], &[0, 0]);
+    let chip8 = draw(with(|q| q.clip = false));
+    assert_eq!(&chip8.framebuffer()[..2], &[1, 1]);
+    assert_eq!(chip8.framebuffer()[WIDTH], 0);
+}
+
+#[test]
+fn display_wait() {
+    // two draws and a jump back, in frames of 100 instructions
+    let rom = [0xD0, 0x01, 0xD0, 0x01, 0x12, 0x00];
+    let cycles = |quirks| {
+        let mut chip8 = Chip8::with_quirks(quirks);
+        chip8.load_rom_bytes(&rom).unwrap();
+        let mut cycles = 0;
+        chip8.run_frame_until(100, |_| {
+            cycles += 1;
+            false
+        })
+        .unwrap();
+        cycles
+    };
+    assert_eq!(cycles(with(|q| q.display_wait = true)), 1);
+    assert_eq!(cycles(with(|q| q.display_wait = false)), 100);
+}
@@ -0,0 +1,81 @@
+// the rewind buffer plays back exactly the states it was given
+mod common;
+
+use chip8::{Chip8, Rewind};
+use common::load_file;
+
+// 30 seconds, like the window
+const CAPACITY: usize = 30 * 60;
+
+// runs the frames recording them, and returns the state of each one
+fn record(chip8: &mut Chip8, rewind: &mut Rewind, frames: usize) -> Vec<Vec<u8>> {
+    let mut states = Vec::new();
+    for _ in 0..frames {
+        chip8.run_frame(10).unwrap();
+        chip8.tick_timers();
+        rewind.push(chip8);
+        states.push(chip8.save_state());
+    }
+    states
+}
+
+#[test]
+fn plays_frames_backward() {
+    let mut chip8 = load_file("BRIX");
+    let mut rewind = Rewind::new(CAPACITY);
+    let states = record(&mut chip8, &mut rewind, 300);
+    assert_eq!(rewind.len(), 299);
+    // much smaller than the states themselves
+    assert!(rewind.size() < states[0].len() * 4);
+
+    for expected in states[..299].iter().rev() {
+        assert!(rewind.rewind(&mut chip8));
+        assert!(chip8.save_state() == *expected);
+    }
+    assert!(!rewind.rewind(&mut chip8));
+    assert!(rewind.is_empty());
+
+    // the emulation goes on from there, and can be rewinded again
+    let states = record(&mut chip8, &mut rewind, 10);
+    assert!(rewind.rewind(&mut chip8));
+    assert!(chip8.save_state() == states[8]);
+}
+
+#[test]
+fn states_of_different_lengths() {
+    // sets a XO-CHIP audio pattern when v0 reaches 200, which makes
+    // the states longer
+    let mut chip8 = Chip8::init();
+    let mut rom = vec![0xA2, 0x0C, 0x70, 0x01, 0x40, 0xC8, 0xF0, 0x02, 0x12, 0x02, 0x00, 0x00];
+    rom.extend_from_slice(&[0xF0; 16]);
+    chip8.load_rom_bytes(&rom).unwrap();
+    let mut rewind = Rewind::new(CAPACITY);
+    let states = record(&mut chip8, &mut rewind, 120);
+    assert!(states[0].len() < states[119].len());
+
+    for expected in states[..119].iter().rev() {
+        assert!(rewind.rewind(&mut chip8));
+        assert!(chip8.save_state() == *expected);
+    }
+    assert!(chip8.audio_pattern().is_none());
+}
+
+#[test]
+fn keeps_only_its_capacity() {
+    let mut chip8 = load_file("BRIX");
+    let mut rewind = Rewind::new(CAPACITY);
+    let states = record(&mut chip8, &mut rewind, CAPACITY + 200);
+    assert_eq!(rewind.len(), CAPACITY);
+
+    for _ in 0..CAPACITY {
+        assert!(rewind.rewind(&mut chip8));
+    }
+    // the oldest frame left is the one 30 seconds before the last
+    assert!(chip8.save_state() == states[199]);
+    assert!(!rewind.rewind(&mut chip8));
+    assert!(chip8.save_state() == states[199]);
+
+    rewind.clear();
+    assert!(rewind.is_empty());
+    assert_eq!(rewind.size(), 0);
+}
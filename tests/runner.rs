@@ -0,0 +1,55 @@
+// the headless runner stops on each of its conditions
+mod common;
+
+use chip8::runner::{Press, Stop};
+use chip8::{Chip8Error, Runner};
+use common::load;
+
+// v0 += 1, skipped while key 5 is held, and a jump back
+const ROM: [u8; 8] = [0x61, 0x05, 0xE1, 0x9E, 0x70, 0x01, 0x12, 0x02];
+
+#[test]
+fn stops() {
+    let mut runner = Runner::new(10);
+    runner.frames = Some(3);
+    let mut chip8 = load(&ROM);
+    assert_eq!(runner.run(&mut chip8), Stop::Frames(3));
+    assert_eq!((runner.frame(), runner.cycles()), (3, 30));
+    assert_eq!(chip8.ticks(), 3);
+
+    let mut runner = Runner::new(10);
+    runner.max_cycles = Some(25);
+    assert_eq!(runner.run(&mut load(&ROM)), Stop::Cycles(25));
+    assert_eq!(runner.frame(), 2);
+
+    let mut runner = Runner::new(10);
+    runner.until_pc = Some(0x206);
+    assert_eq!(runner.run(&mut load(&ROM)), Stop::Pc(0x206));
+    assert_eq!(runner.cycles(), 3);
+    let mut runner = Runner::new(10);
+    runner.until_pc = Some(0x200);
+    assert_eq!(runner.run(&mut load(&ROM)), Stop::Pc(0x200));
+    assert_eq!(runner.cycles(), 0);
+
+    let mut runner = Runner::new(10);
+    runner.until_opcode = Some(0x1202);
+    assert_eq!(runner.run(&mut load(&ROM)), Stop::Opcode(0x1202));
+
+    assert_eq!(Runner::new(10).run(&mut load(&[0x00, 0xFD])), Stop::Halted);
+    let stop = Runner::new(10).run(&mut load(&[0x00, 0xEE]));
+    assert_eq!(stop, Stop::Error(Chip8Error::StackUnderflow { pc: 0x200, opcode: 0x00EE }));
+    assert_eq!(Stop::Pc(0x206).to_string(), "pc reached 0x206");
+}
+
+#[test]
+fn presses() {
+    // held from the second frame for 2 frames
+    let mut runner = Runner::new(9);
+    runner.frames = Some(4);
+    runner.presses.push(Press { frame: 1, key: 5, frames: 2 });
+    let mut chip8 = load(&ROM);
+    runner.run(&mut chip8);
+    assert!(!chip8.is_key_pressed(5));
+    // 3 adds in the first frame and 3 in the last one
+    assert_eq!(chip8.registers()[0], 6);
+}
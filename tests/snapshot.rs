@@ -0,0 +1,40 @@
+// the screen exported in each format, the PNG is read back with the
+// decoder of the png crate
+use chip8::snapshot::{to_pbm, to_pgm, to_png};
+
+// 3x2, with each XO-CHIP colour and a pixel lit on both planes
+const FRAMEBUFFER: [u8; 6] = [0, 1, 2, 3, 0, 1];
+
+#[test]
+fn pbm() {
+    assert_eq!(to_pbm(&FRAMEBUFFER, 3), b"P1\n3 2\n011\n101\n".to_vec());
+}
+
+#[test]
+fn pgm() {
+    let mut expected = b"P5\n3 2\n255\n".to_vec();
+    expected.extend_from_slice(&[0, 255, 170, 85, 0, 255]);
+    assert_eq!(to_pgm(&FRAMEBUFFER, 3), expected);
+}
+
+#[test]
+fn png() {
+    let data = to_png(&FRAMEBUFFER, 3);
+    let mut reader = png::Decoder::new(&data[..]).read_info().unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).unwrap();
+    assert_eq!((info.width, info.height), (3, 2));
+    assert_eq!((info.color_type, info.bit_depth), (png::ColorType::Grayscale, png::BitDepth::Eight));
+    assert_eq!(&pixels[..info.buffer_size()], &[0, 255, 170, 85, 0, 255]);
+}
+
+#[test]
+fn full_screen() {
+    // the largest screen, all of it lit
+    let data = to_png(&[1; 128 * 64], 128);
+    let mut reader = png::Decoder::new(&data[..]).read_info().unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).unwrap();
+    assert_eq!((info.width, info.height), (128, 64));
+    assert!(pixels[..info.buffer_size()].iter().all(|pixel| *pixel == 255));
+}
@@ -0,0 +1,92 @@
+// save states: a state loaded back gives the same machine, and the
+// ones that can't be loaded are rejected without touching it
+mod common;
+
+use chip8::{Chip8, StateError};
+use common::load_file;
+
+fn run(chip8: &mut Chip8, frames: usize) {
+    for _ in 0..frames {
+        chip8.run_frame(10).unwrap();
+        chip8.tick_timers();
+    }
+}
+
+#[test]
+fn round_trip() {
+    let mut chip8 = load_file("BRIX");
+    run(&mut chip8, 60);
+    let state = chip8.save_state();
+    let registers = *chip8.registers();
+    // the random numbers aren't part of the state
+    chip8.seed(9);
+    run(&mut chip8, 60);
+    let later = chip8.save_state();
+
+    // back in time, in the same machine and in another one
+    chip8.load_state(&state).unwrap();
+    assert_eq!(*chip8.registers(), registers);
+    assert!(chip8.save_state() == state);
+    let mut other = load_file("BRIX");
+    other.load_state(&state).unwrap();
+    assert!(other.save_state() == state);
+
+    // and from there it runs the same way again
+    chip8.seed(9);
+    run(&mut chip8, 60);
+    assert!(chip8.save_state() == later);
+}
+
+#[test]
+fn rejected_states() {
+    let mut chip8 = load_file("BRIX");
+    run(&mut chip8, 30);
+    let state = chip8.save_state();
+    run(&mut chip8, 30);
+    let before = chip8.save_state();
+
+    let mut magic = state.clone();
+    magic[0] = b'X';
+    assert_eq!(chip8.load_state(&magic), Err(StateError::Corrupted));
+
+    let mut version = state.clone();
+    version[4] = 2;
+    assert_eq!(
+        chip8.load_state(&version),
+        Err(StateError::Version {
+            found: 2,
+            expected: 1
+        })
+    );
+
+    assert_eq!(chip8.load_state(&state[..state.len() - 1]), Err(StateError::Corrupted));
+    assert_eq!(chip8.load_state(&state[..10]), Err(StateError::Corrupted));
+    assert_eq!(chip8.load_state(&[]), Err(StateError::Corrupted));
+    let mut longer = state.clone();
+    longer.push(0);
+    assert_eq!(chip8.load_state(&longer), Err(StateError::Corrupted));
+
+    // the SHA-1 of the rom follows the magic and the version
+    let mut other = Chip8::init();
+    other.load_rom_bytes(&[0x12, 0x00]).unwrap();
+    assert_eq!(other.load_state(&state), Err(StateError::RomMismatch));
+    let mut hash = state.clone();
+    hash[6] ^= 1;
+    assert_eq!(chip8.load_state(&hash), Err(StateError::RomMismatch));
+
+    // none of them changed anything
+    assert!(chip8.save_state() == before);
+}
+
+#[test]
+fn oversized_timers() {
+    // the setters keep the timers in a byte, so saving doesn't fail
+    let mut chip8 = load_file("BRIX");
+    chip8.set_delay_timer(300);
+    chip8.set_sound_timer(0x1FF);
+    assert_eq!((chip8.delay_timer(), chip8.sound_timer()), (300 & 0xFF, 0xFF));
+    let state = chip8.save_state();
+    let mut other = load_file("BRIX");
+    other.load_state(&state).unwrap();
+    assert_eq!((other.delay_timer(), other.sound_timer()), (300 & 0xFF, 0xFF));
+}
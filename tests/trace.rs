@@ -0,0 +1,79 @@
+// the trace is meant to be diffed, its format has to stay put
+use chip8::{Chip8, Tracer};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+// a writer the test can still read once it's given to the tracer
+#[derive(Clone, Default)]
+struct Shared(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Shared {
+    fn lines(&self) -> Vec<String> {
+        let text = String::from_utf8(self.0.lock().unwrap().clone()).unwrap();
+        text.lines().map(String::from).collect()
+    }
+}
+
+// V0 := 5, V1 := 1, then counts V1 up to V0 and hits an invalid opcode
+const ROM: [u8; 12] = [0x60, 0x05, 0x61, 0x01, 0x71, 0x01, 0x51, 0x00, 0x12, 0x04, 0x51, 0x21];
+
+#[test]
+fn every_instruction() {
+    let output = Shared::default();
+    let mut chip8 = Chip8::init();
+    chip8.load_rom_bytes(&ROM).unwrap();
+    chip8.set_tracer(Tracer::new(Box::new(output.clone())));
+    chip8.run_frame(3).unwrap();
+
+    let lines = output.lines();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(
+        lines[2],
+        "00000002 0204 7101 ADD V1, 0x01         05 01 00 00 00 00 00 00 00 00 00 00 00 00 00 00 I:0000 SP:0 DT:00 ST:00"
+    );
+    assert_eq!(chip8.take_tracer().unwrap().cycle(), 3);
+}
+
+#[test]
+fn address_range() {
+    let output = Shared::default();
+    let mut chip8 = Chip8::init();
+    chip8.load_rom_bytes(&ROM).unwrap();
+    chip8.set_tracer(Tracer::new(Box::new(output.clone())).with_range(0x204, 0x205));
+    chip8.run_frame(100).unwrap_err();
+
+    // the cycles still count the instructions left out
+    let cycles: Vec<String> = output.lines().iter().map(|l| l[..13].to_string()).collect();
+    assert_eq!(
+        cycles,
+        ["00000002 0204", "00000005 0204", "00000008 0204", "00000011 0204"]
+    );
+}
+
+#[test]
+fn ring_dumped_on_error() {
+    let output = Shared::default();
+    let mut chip8 = Chip8::init();
+    chip8.load_rom_bytes(&ROM).unwrap();
+    chip8.set_tracer(Tracer::ring(Box::new(output.clone()), 3));
+    chip8.run_frame(8).unwrap();
+    // nothing is written while the rom runs fine
+    assert!(output.lines().is_empty());
+
+    chip8.run_frame(100).unwrap_err();
+    let lines = output.lines();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("00000011 0204 7101 ADD V1, 0x01"));
+    assert!(lines[1].starts_with("00000012 0206 5100 SE V1, V0"));
+    assert!(lines[2].starts_with("00000013 020A 5121 DW 0x5121"));
+}